@@ -0,0 +1,472 @@
+// rouler - A container-based system for generating die rolls
+// Copyright (C) 2016 by John Berry
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use error::{Error, Limit};
+use expr::Op;
+use roll::Comparison;
+
+const MAX_OUTCOMES: u64 = 100_000;
+const MAX_WORK: u64 = 100_000_000;
+
+/// The exact chance of every total that a die roll can produce.
+///
+/// A `Distribution` is worked out from the roll's syntax with [`distribution`](fn.distribution.html),
+/// or from a parsed roll with `Expr::distribution`, without rolling any dice.
+///
+/// # Examples
+///
+/// ```
+/// let stat = rouler::distribution("4d6kh3").unwrap();
+///
+/// assert!((stat.probability(18) - 21.0 / 1296.0).abs() < 1e-12);
+/// assert_eq!(stat.probability(2), 0.0);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution {
+    /// The lowest possible outcome.
+    min: i64,
+    /// The chance of each outcome counting up from `min`. Neither the first nor the last is ever zero.
+    probabilities: Vec<f64>,
+}
+
+/// The chances of each outcome of a contest between two rolls, from the point of view of the first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Odds {
+    /// The chance of the first roll's total being higher.
+    pub win: f64,
+    /// The chance of the two totals being equal.
+    pub tie: f64,
+    /// The chance of the first roll's total being lower.
+    pub lose: f64,
+}
+
+impl Distribution {
+    /// The chance of the roll totalling exactly `value`.
+    pub fn probability(&self, value: i64) -> f64 {
+        let offset = value as i128 - self.min as i128;
+        if offset < 0 || offset >= self.probabilities.len() as i128 {
+            0.0
+        } else {
+            self.probabilities[offset as usize]
+        }
+    }
+
+    /// Iterates over every possible total in ascending order, along with its chance.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let coin = rouler::distribution("1d2 * 10").unwrap();
+    ///
+    /// assert_eq!(coin.iter().collect::<Vec<(i64, f64)>>(), vec![(10, 0.5), (20, 0.5)]);
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (i64, f64)> + '_ {
+        let min = self.min;
+        self.probabilities.iter()
+            .enumerate()
+            .filter(|&(_, &probability)| probability > 0.0)
+            .map(move |(offset, &probability)| (min + offset as i64, probability))
+    }
+
+    /// The chance of the total comparing to `threshold` in the given way.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rouler::Comparison;
+    ///
+    /// let attack = rouler::distribution("1d20 + 7").unwrap();
+    ///
+    /// assert!((attack.chance(Comparison::GTE, 16) - 0.6).abs() < 1e-12);
+    /// ```
+    pub fn chance(&self, comparison: Comparison, threshold: i64) -> f64 {
+        self.iter()
+            .filter(|&(value, _)| comparison.matches(value, threshold))
+            .map(|(_, probability)| probability)
+            .sum()
+    }
+
+    /// Works out the chances of this roll beating, tying with and losing to an opposing roll.
+    pub fn against(&self, other: &Distribution) -> Odds {
+        // `below[i]` is the chance of the opposing total being lower than its `i`th outcome
+        let below = other.probabilities.iter()
+            .scan(0.0, |below, probability| {
+                let lower = *below;
+                *below += probability;
+                Some(lower)
+            })
+            .collect::<Vec<f64>>();
+        let other_mass = other.mass();
+
+        let mut odds = Odds { win: 0.0, tie: 0.0, lose: 0.0 };
+        for (value, probability) in self.iter() {
+            let offset = value as i128 - other.min as i128;
+            let (lower, equal) = if offset < 0 {
+                (0.0, 0.0)
+            } else if offset >= other.probabilities.len() as i128 {
+                (other_mass, 0.0)
+            } else {
+                (below[offset as usize], other.probabilities[offset as usize])
+            };
+            odds.win += probability * lower;
+            odds.tie += probability * equal;
+            odds.lose += probability * (other_mass - lower - equal).max(0.0);
+        }
+        odds
+    }
+
+    /// The lowest possible total.
+    pub fn min(&self) -> i64 {
+        self.min
+    }
+
+    /// The highest possible total.
+    pub fn max(&self) -> i64 {
+        self.min + self.probabilities.len() as i64 - 1
+    }
+
+    /// The average total.
+    pub fn mean(&self) -> f64 {
+        self.iter().map(|(value, probability)| value as f64 * probability).sum()
+    }
+
+    /// The variance of the total: the average squared distance of the total from the mean.
+    pub fn variance(&self) -> f64 {
+        let mean = self.mean();
+        self.iter().map(|(value, probability)| (value as f64 - mean).powi(2) * probability).sum()
+    }
+
+    /// The standard deviation of the total.
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    /// The lowest total that at least `p` percent of rolls are no higher than, so that `percentile(50.0)`
+    /// is the median. `p` is clamped to between 0 and 100.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let check = rouler::distribution("1d20").unwrap();
+    ///
+    /// assert_eq!(check.percentile(0.0), 1);
+    /// assert_eq!(check.percentile(50.0), 10);
+    /// assert_eq!(check.percentile(90.0), 18);
+    /// assert_eq!(check.percentile(100.0), 20);
+    /// ```
+    pub fn percentile(&self, p: f64) -> i64 {
+        // allow for rounding error in the running total, so that exact percentiles land on the right total
+        let target = p.clamp(0.0, 100.0) / 100.0 - 1e-12;
+        let mut cumulative = 0.0;
+        for (value, probability) in self.iter() {
+            cumulative += probability;
+            if cumulative >= target {
+                return value;
+            }
+        }
+        self.max()
+    }
+
+    /// A distribution which is always `value`.
+    pub(crate) fn constant(value: i64) -> Self {
+        Distribution { min: value, probabilities: vec![1.0] }
+    }
+
+    /// A distribution with no outcomes at all, used to build up a distribution from its parts.
+    pub(crate) fn empty() -> Self {
+        Distribution { min: 0, probabilities: Vec::new() }
+    }
+
+    /// An equal chance of each of `faces`, so that a face listed twice is twice as likely.
+    pub(crate) fn uniform(faces: &[i64]) -> Result<Self, Error> {
+        let chance = 1.0 / faces.len() as f64;
+        Self::from_outcomes(faces.iter().map(|&face| (face, chance)))
+    }
+
+    /// An equal chance of every number from `low` to `high` inclusive.
+    pub(crate) fn between(low: i64, high: i64) -> Result<Self, Error> {
+        let len = outcomes_between(low, high)?;
+        Ok(Distribution { min: low, probabilities: vec![1.0 / len as f64; len] })
+    }
+
+    pub(crate) fn from_outcomes<I>(outcomes: I) -> Result<Self, Error>
+        where I: IntoIterator<Item = (i64, f64)>
+    {
+        let outcomes = outcomes.into_iter()
+            .filter(|&(_, probability)| probability > 0.0)
+            .collect::<Vec<(i64, f64)>>();
+        let min = match outcomes.iter().map(|&(value, _)| value).min() {
+            Some(min) => min,
+            None => return Ok(Self::empty()),
+        };
+        let max = outcomes.iter().map(|&(value, _)| value).max().unwrap();
+
+        let mut probabilities = vec![0.0; outcomes_between(min, max)?];
+        for (value, probability) in outcomes {
+            probabilities[(value - min) as usize] += probability;
+        }
+        Ok(Distribution { min, probabilities })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.probabilities.is_empty()
+    }
+
+    /// The combined chance of every outcome, which is less than one for part of a distribution.
+    pub(crate) fn mass(&self) -> f64 {
+        self.probabilities.iter().sum()
+    }
+
+    /// The part of the distribution for which `keep` is true, with the chances left as they are.
+    pub(crate) fn restrict<F>(&self, keep: F) -> Self
+        where F: Fn(i64) -> bool
+    {
+        let mut restricted = self.clone();
+        for (offset, probability) in restricted.probabilities.iter_mut().enumerate() {
+            if !keep(self.min + offset as i64) {
+                *probability = 0.0;
+            }
+        }
+        restricted.trimmed()
+    }
+
+    fn trimmed(mut self) -> Self {
+        while self.probabilities.last() == Some(&0.0) {
+            self.probabilities.pop();
+        }
+        let leading = self.probabilities.iter().take_while(|&&probability| probability == 0.0).count();
+        self.probabilities.drain(..leading);
+        self.min += leading as i64;
+        self
+    }
+
+    pub(crate) fn scale(&self, factor: f64) -> Self {
+        Distribution {
+            min: self.min,
+            probabilities: self.probabilities.iter().map(|probability| probability * factor).collect(),
+        }.trimmed()
+    }
+
+    /// Scales the distribution so that its chances add up to one.
+    pub(crate) fn normalized(&self) -> Self {
+        self.scale(1.0 / self.mass())
+    }
+
+    /// Adds the chances of `other` to this distribution.
+    pub(crate) fn mix(&self, other: &Self) -> Result<Self, Error> {
+        if self.is_empty() {
+            return Ok(other.clone());
+        }
+        Self::from_outcomes(self.iter().chain(other.iter()))
+    }
+
+    pub(crate) fn map<F>(&self, f: F) -> Result<Self, Error>
+        where F: Fn(i64) -> Result<i64, Error>
+    {
+        let outcomes = self.iter()
+            .map(|(value, probability)| Ok((f(value)?, probability)))
+            .collect::<Result<Vec<(i64, f64)>, Error>>()?;
+        Self::from_outcomes(outcomes)
+    }
+
+    /// The distribution of the sum of an outcome from this distribution and one from `other`.
+    pub(crate) fn convolve(&self, other: &Self) -> Result<Self, Error> {
+        self.convolve_within(other, &mut Work::default())
+    }
+
+    fn convolve_within(&self, other: &Self, work: &mut Work) -> Result<Self, Error> {
+        if self.is_empty() || other.is_empty() {
+            return Ok(Self::empty());
+        }
+        let min = self.min.checked_add(other.min).ok_or(Error::Overflow)?;
+        let max = self.max().checked_add(other.max()).ok_or(Error::Overflow)?;
+
+        let mut probabilities = vec![0.0; outcomes_between(min, max)?];
+        work.spend(self.probabilities.len() * other.probabilities.len())?;
+        for (i, lhs) in self.probabilities.iter().enumerate() {
+            for (j, rhs) in other.probabilities.iter().enumerate() {
+                probabilities[i + j] += lhs * rhs;
+            }
+        }
+        Ok(Distribution { min, probabilities }.trimmed())
+    }
+
+    /// Adds the chances of `other`, moved up by `offset` and scaled by `factor`, to this distribution.
+    fn accumulate(&mut self, other: &Self, offset: i64, factor: f64) -> Result<(), Error> {
+        if other.is_empty() {
+            return Ok(());
+        }
+        let min = other.min.checked_add(offset).ok_or(Error::Overflow)?;
+        let max = other.max().checked_add(offset).ok_or(Error::Overflow)?;
+        if self.is_empty() {
+            *self = Distribution { min, probabilities: vec![0.0; outcomes_between(min, max)?] };
+        } else {
+            let len = outcomes_between(self.min.min(min), self.max().max(max))?;
+            if min < self.min {
+                let below = (self.min - min) as usize;
+                self.probabilities.splice(0..0, vec![0.0; below]);
+                self.min = min;
+            }
+            self.probabilities.resize(len, 0.0);
+        }
+        let start = (min - self.min) as usize;
+        for (slot, probability) in self.probabilities[start..].iter_mut().zip(&other.probabilities) {
+            *slot += probability * factor;
+        }
+        Ok(())
+    }
+
+    /// The distribution of `op` applied to an outcome from this distribution and one from `other`.
+    ///
+    /// An error is returned if any pair of outcomes fails, such as by dividing by zero.
+    pub(crate) fn combine(&self, op: Op, other: &Self) -> Result<Self, Error> {
+        match op {
+            Op::Add => self.convolve(other),
+            Op::Subtract => self.convolve(&other.map(|value| value.checked_neg().ok_or(Error::Overflow))?),
+            _ => self.pairwise(other, |lhs, rhs| op.apply(lhs, rhs)),
+        }
+    }
+
+    /// The distribution of `f(lhs, rhs)` for an outcome `lhs` from this distribution and `rhs` from `other`.
+    pub(crate) fn pairwise<F>(&self, other: &Self, f: F) -> Result<Self, Error>
+        where F: Fn(i64, i64) -> Result<i64, Error>
+    {
+        Self::from_outcomes(pairwise(&self.iter().collect::<Vec<_>>(), &other.iter().collect::<Vec<_>>(), f)?)
+    }
+
+    /// The distribution of the sum of `times` outcomes from this distribution.
+    pub(crate) fn repeat(&self, times: u64) -> Result<Self, Error> {
+        if !self.is_empty() {
+            let times = i64::try_from(times).map_err(|_| Error::Overflow)?;
+            let min = self.min.checked_mul(times).ok_or(Error::Overflow)?;
+            let max = self.max().checked_mul(times).ok_or(Error::Overflow)?;
+            outcomes_between(min, max)?;
+        }
+
+        // Worked out by squaring, so that the sum of `times` outcomes takes only about `log2(times)` steps
+        let mut work = Work::default();
+        let mut total = Self::constant(0);
+        let mut power = self.clone();
+        let mut times = times;
+        while times > 0 {
+            if times & 1 == 1 {
+                total = total.convolve_within(&power, &mut work)?;
+            }
+            times >>= 1;
+            if times > 0 {
+                power = power.convolve_within(&power, &mut work)?;
+            }
+        }
+        Ok(total)
+    }
+
+    /// The distribution of the total of `keep` out of `count` dice rolled from this distribution, keeping
+    /// the highest dice if `highest` is true, or the lowest otherwise. Each kept die adds `score(face)`.
+    pub(crate) fn keep<F>(&self, count: u64, keep: u64, highest: bool, score: F) -> Result<Self, Error>
+        where F: Fn(i64) -> i64
+    {
+        let count = count as usize;
+        let keep = keep.min(count as u64) as usize;
+        let ln_factorial = (0..=count)
+            .scan(0.0, |ln_factorial, n| {
+                if n > 0 {
+                    *ln_factorial += (n as f64).ln();
+                }
+                Some(*ln_factorial)
+            })
+            .collect::<Vec<f64>>();
+        let ln_choose = |n: usize, r: usize| ln_factorial[n] - ln_factorial[r] - ln_factorial[n - r];
+
+        // Faces are visited best first, so the first dice placed on them are the ones kept
+        let mut faces = self.iter().collect::<Vec<(i64, f64)>>();
+        if highest {
+            faces.reverse();
+        }
+
+        // `placed[n]` is the chance of each kept total with `n` of the dice showing the faces visited so far.
+        // Once `keep` dice are placed the total can't change, so `placed[keep]` gathers every such case.
+        let mut work = Work::default();
+        let mut unvisited = self.mass();
+        let mut placed = vec![Self::empty(); keep + 1];
+        placed[0] = Self::constant(0);
+        for (index, &(face, probability)) in faces.iter().enumerate() {
+            // The chance of a die not yet placed showing this face rather than one still to come
+            let chance = if index + 1 == faces.len() { 1.0 } else { (probability / unvisited).min(1.0) };
+            unvisited -= probability;
+
+            let mut next = vec![Self::empty(); keep + 1];
+            next[keep] = placed[keep].clone();
+            for (n, totals) in placed.iter().enumerate().take(keep).filter(|&(_, totals)| !totals.is_empty()) {
+                let left = count - n;
+                for showing in 0..=left {
+                    // Computed in logs, since both the binomial coefficient and the powers can be out of range
+                    let binomial = if chance >= 1.0 {
+                        if showing == left { 1.0 } else { 0.0 }
+                    } else {
+                        (ln_choose(left, showing) + showing as f64 * chance.ln()
+                            + (left - showing) as f64 * (-chance).ln_1p()).exp()
+                    };
+                    if binomial == 0.0 {
+                        continue;
+                    }
+                    let kept = showing.min(keep - n);
+                    let added = score(face).checked_mul(kept as i64).ok_or(Error::Overflow)?;
+                    work.spend(totals.probabilities.len())?;
+                    next[n + kept].accumulate(totals, added, binomial)?;
+                }
+            }
+            placed = next;
+        }
+        Ok(placed.pop().unwrap().trimmed())
+    }
+}
+
+/// The steps taken to work out a distribution, counted so that no roll can take too long.
+#[derive(Default)]
+struct Work(u64);
+
+impl Work {
+    fn spend(&mut self, steps: usize) -> Result<(), Error> {
+        self.0 = self.0.saturating_add(steps as u64);
+        if self.0 > MAX_WORK {
+            Err(Error::LimitExceeded { limit: Limit::Work, max: MAX_WORK })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The chance of each result of `f(lhs, rhs)` for an outcome `lhs` from `lhs` and `rhs` from `rhs`, where the
+/// outcomes needn't be whole numbers.
+pub(crate) fn pairwise<T, F>(lhs: &[(T, f64)], rhs: &[(T, f64)], f: F) -> Result<Vec<(T, f64)>, Error>
+    where T: Ord + Copy, F: Fn(T, T) -> Result<T, Error>
+{
+    let mut outcomes = BTreeMap::new();
+    Work::default().spend(lhs.len() * rhs.len())?;
+    for &(lhs, lhs_probability) in lhs {
+        for &(rhs, rhs_probability) in rhs {
+            *outcomes.entry(f(lhs, rhs)?).or_insert(0.0) += lhs_probability * rhs_probability;
+        }
+        if outcomes.len() as u64 > MAX_OUTCOMES {
+            return Err(Error::LimitExceeded { limit: Limit::Outcomes, max: MAX_OUTCOMES });
+        }
+    }
+    Ok(outcomes.into_iter().collect())
+}
+
+/// The number of outcomes from `low` to `high` inclusive, if it is within the limit.
+fn outcomes_between(low: i64, high: i64) -> Result<usize, Error> {
+    let len = high as i128 - low as i128 + 1;
+    if len > MAX_OUTCOMES as i128 {
+        Err(Error::LimitExceeded { limit: Limit::Outcomes, max: MAX_OUTCOMES })
+    } else {
+        Ok(len as usize)
+    }
+}
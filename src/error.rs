@@ -0,0 +1,147 @@
+// rouler - A container-based system for generating die rolls
+// Copyright (C) 2016 by John Berry
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::error;
+use std::fmt;
+use std::ops::Range;
+
+use pest::error::{Error as PestError, ErrorVariant, InputLocation};
+use parse::Rule;
+
+/// The ways in which reading or rolling a die roll can fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The input is not a valid die roll.
+    Syntax {
+        /// The byte range of the input at which parsing failed.
+        span: Range<usize>,
+        /// Descriptions of the tokens that would have been accepted at `span`.
+        expected: Vec<String>,
+    },
+    /// The roll divided by zero.
+    DivisionByZero,
+    /// The roll produced a number too large to be represented.
+    Overflow,
+    /// The roll asked for more than one of the limits on rolling allows.
+    LimitExceeded {
+        /// Which limit was exceeded.
+        limit: Limit,
+        /// The largest value the limit allows.
+        max: u64,
+    },
+    /// The distribution of the roll can't be worked out, as for exploding dice that are also kept or dropped.
+    Unsupported,
+    /// A recorded roll log doesn't match the roll it is replayed into.
+    ReplayMismatch {
+        /// The position in the log of the first draw that doesn't match.
+        draw: usize,
+    },
+    /// A seed for a fair roll isn't 64 hexadecimal digits.
+    InvalidSeed,
+    /// The roll refers to a variable that hasn't been given a value.
+    UnknownVariable {
+        /// The name of the variable, without its `@`.
+        name: String,
+    },
+}
+
+/// The limits placed on a single roll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// The number of dice rolled by a single die code.
+    Dice,
+    /// The number of dice rolled by a whole expression.
+    TotalDice,
+    /// The number of sides on a single die.
+    Sides,
+    /// The number of faces listed on a single custom die.
+    CustomSides,
+    /// How deeply parentheses, function arguments and unary minus signs are nested.
+    Depth,
+    /// The number of times the dice of a single die code explode.
+    Explosions,
+    /// The number of different totals in a distribution.
+    Outcomes,
+    /// The number of steps taken to work out a distribution.
+    Work,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Syntax { ref span, ref expected } => {
+                write!(f, "invalid roll at position {}", span.start)?;
+                if !expected.is_empty() {
+                    write!(f, ", expected {}", expected.join(" or "))?;
+                }
+                Ok(())
+            },
+            Error::DivisionByZero => write!(f, "division by zero"),
+            Error::Overflow => write!(f, "result is too large"),
+            Error::LimitExceeded { limit, max } => write!(f, "too many {} (the limit is {})", limit, max),
+            Error::Unsupported => write!(f, "the distribution of this roll can't be worked out"),
+            Error::ReplayMismatch { draw } => write!(f, "the roll log doesn't match the roll at draw {}", draw),
+            Error::InvalidSeed => write!(f, "the seed isn't 64 hexadecimal digits"),
+            Error::UnknownVariable { ref name } => write!(f, "unknown variable @{{{}}}", name),
+        }
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Limit::Dice => "dice",
+            Limit::TotalDice => "dice in total",
+            Limit::Sides => "sides",
+            Limit::CustomSides => "custom die faces",
+            Limit::Depth => "levels of nesting",
+            Limit::Explosions => "explosions",
+            Limit::Outcomes => "possible totals",
+            Limit::Work => "steps to work out the distribution",
+        };
+        f.write_str(name)
+    }
+}
+
+impl error::Error for Error {}
+
+/// Converts a pest parsing error into a `Syntax` error.
+pub fn syntax_error(err: PestError<Rule>) -> Error {
+    let span = match err.location {
+        InputLocation::Pos(pos) => pos..pos,
+        InputLocation::Span((start, end)) => start..end,
+    };
+    let expected = match err.variant {
+        ErrorVariant::ParsingError { positives, .. } => positives.into_iter().map(describe).collect(),
+        ErrorVariant::CustomError { .. } => Vec::new(),
+    };
+
+    Error::Syntax { span, expected }
+}
+
+fn describe(rule: Rule) -> String {
+    let description = match rule {
+        Rule::expr => "an expression",
+        Rule::roll | Rule::die => "a die roll",
+        Rule::int | Rule::uint | Rule::count => "a number",
+        Rule::variable | Rule::name => "a variable",
+        Rule::call | Rule::floor | Rule::ceil | Rule::round | Rule::abs | Rule::min | Rule::max => "a function call",
+        Rule::plus => "`+`",
+        Rule::minus => "`-`",
+        Rule::times => "`*`",
+        Rule::slash => "`/`",
+        Rule::modulo => "`%`",
+        Rule::power => "`**` or `^`",
+        Rule::negation => "`-`",
+        Rule::gt | Rule::gte | Rule::lt | Rule::lte | Rule::eq => "a comparison",
+        Rule::and => "`and`",
+        Rule::or => "`or`",
+        Rule::EOI => "the end of the roll",
+        rule => return format!("{:?}", rule),
+    };
+    description.to_string()
+}
@@ -0,0 +1,128 @@
+// rouler - A container-based system for generating die rolls
+// Copyright (C) 2016 by John Berry
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::fmt::Write;
+
+use distribution::Distribution;
+use simulate::Histogram;
+
+/// A possible total, along with its chance and the chance of any total up to and including it.
+struct Row {
+    value: i64,
+    probability: f64,
+    cumulative: f64,
+}
+
+fn rows<I>(outcomes: I) -> Vec<Row>
+    where I: IntoIterator<Item = (i64, f64)>
+{
+    let mut cumulative = 0.0;
+    outcomes.into_iter()
+        .map(|(value, probability)| {
+            cumulative += probability;
+            Row { value, probability, cumulative }
+        })
+        .collect()
+}
+
+fn chart(rows: &[Row], width: usize) -> String {
+    let value_width = rows.iter().map(|row| row.value.to_string().len()).max().unwrap_or(0);
+    let highest = rows.iter().map(|row| row.probability).fold(0.0, f64::max);
+
+    let mut chart = String::new();
+    for row in rows {
+        let bar = (row.probability / highest * width as f64).round() as usize;
+        let line = format!("{:>value_width$} {:>6.2}% {}", row.value, row.probability * 100.0, "#".repeat(bar),
+                           value_width = value_width);
+        writeln!(chart, "{}", line.trim_end()).unwrap();
+    }
+    chart
+}
+
+fn csv(rows: &[Row]) -> String {
+    let mut csv = String::from("value,probability,cumulative\n");
+    for row in rows {
+        writeln!(csv, "{},{},{}", row.value, row.probability, row.cumulative).unwrap();
+    }
+    csv
+}
+
+fn json(rows: &[Row]) -> String {
+    let rows = rows.iter()
+        .map(|row| format!(r#"{{"value":{},"probability":{},"cumulative":{}}}"#, row.value, row.probability, row.cumulative))
+        .collect::<Vec<String>>();
+    format!("[{}]", rows.join(","))
+}
+
+impl Distribution {
+    /// Renders the distribution as a bar chart for terminals and chat, with one line per total showing
+    /// its chance as a percentage and a bar of up to `width` characters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let coin = rouler::distribution("1d2").unwrap();
+    ///
+    /// assert_eq!(coin.chart(10), "1  50.00% ##########\n2  50.00% ##########\n");
+    /// ```
+    pub fn chart(&self, width: usize) -> String {
+        chart(&rows(self.iter()), width)
+    }
+
+    /// Renders the distribution as CSV, with a header followed by one `value,probability,cumulative` row
+    /// per total.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let coin = rouler::distribution("1d2").unwrap();
+    ///
+    /// assert_eq!(coin.csv(), "value,probability,cumulative\n1,0.5,0.5\n2,0.5,1\n");
+    /// ```
+    pub fn csv(&self) -> String {
+        csv(&rows(self.iter()))
+    }
+
+    /// Renders the distribution as a JSON array of objects with `value`, `probability` and `cumulative`
+    /// fields.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let coin = rouler::distribution("1d2").unwrap();
+    ///
+    /// assert_eq!(
+    ///     coin.json(),
+    ///     r#"[{"value":1,"probability":0.5,"cumulative":0.5},{"value":2,"probability":0.5,"cumulative":1}]"#
+    /// );
+    /// ```
+    pub fn json(&self) -> String {
+        json(&rows(self.iter()))
+    }
+}
+
+impl Histogram {
+    fn rows(&self) -> Vec<Row> {
+        rows(self.iter().map(|(value, _)| (value, self.probability(value))))
+    }
+
+    /// Renders the histogram as a bar chart, as for `Distribution::chart`, using the fraction of rolls
+    /// that gave each total.
+    pub fn chart(&self, width: usize) -> String {
+        chart(&self.rows(), width)
+    }
+
+    /// Renders the histogram as CSV, as for `Distribution::csv`.
+    pub fn csv(&self) -> String {
+        csv(&self.rows())
+    }
+
+    /// Renders the histogram as JSON, as for `Distribution::json`.
+    pub fn json(&self) -> String {
+        json(&self.rows())
+    }
+}
@@ -0,0 +1,743 @@
+// rouler - A container-based system for generating die rolls
+// Copyright (C) 2016 by John Berry
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use rand::{thread_rng, Rng};
+
+use distribution::{self, Distribution, Odds};
+use error::Error;
+use limits::{Limits, Warning};
+use parse::parse_expr;
+use record::{Recorder, Replayer, RollLog};
+use result::RollResult;
+use roll::{Comparison, DieSource, Roll};
+use simulate::Histogram;
+use variables::Variables;
+
+/// A parsed die roll, ready to be rolled any number of times without parsing it again.
+///
+/// An `Expr` is created with [`parse`](fn.parse.html), and rolled with `eval`.
+///
+/// # Examples
+///
+/// ```
+/// use rouler::parse;
+///
+/// let damage = parse("2d6 + 3").unwrap();
+/// let rolls = (0..1000).map(|_| damage.eval().unwrap()).collect::<Vec<i64>>();
+///
+/// assert!(rolls.iter().all(|&roll| 5 <= roll && roll <= 15));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Expr {
+    root: Node,
+    source: String,
+    /// The dice the expression may roll through rerolls and explosions before it reaches its `total_dice`.
+    extra_dice: u64,
+    warnings: Vec<Warning>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Node {
+    Number(i64),
+    Roll(Roll),
+    Variable(String),
+    Negation(Box<Node>),
+    Operation(Op, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+/// The operators that can appear in a die roll.
+///
+/// Comparisons, `and` and `or` are 1 if they hold and 0 if not, so that they can be counted or combined with
+/// the rest of the roll. Any value other than 0 counts as holding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// `+`
+    Add,
+    /// `-`
+    Subtract,
+    /// `*`
+    Multiply,
+    /// `/`, which rounds toward zero.
+    Divide,
+    /// `%`, the remainder of a division, which is never negative, so that `-1 % 12` is 11.
+    Modulo,
+    /// `**` or `^`, raising the left operand to the power of the right. A negative power rounds toward zero
+    /// as `/` does, so `2 ^ -1` is 0.
+    Power,
+    /// A comparison between two totals, such as `1d20 + 5 >= 15`.
+    Compare(Comparison),
+    /// `and`, which holds if both operands do.
+    And,
+    /// `or`, which holds if either operand does.
+    Or,
+}
+
+/// The functions that can be called in a die roll.
+///
+/// As `/` rounds toward zero, `floor`, `ceil` and `round` change how a division is rounded when it is
+/// within their argument, as in `floor(1d6 / 2)`. Their argument is worked out exactly through `+`, `-`,
+/// `*` and `/`, so that only the final value is rounded, and `floor(-7 / 2 + 0)` is -4.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    /// `floor(x)`, which rounds `x` down.
+    Floor,
+    /// `ceil(x)`, which rounds `x` up.
+    Ceil,
+    /// `round(x)`, which rounds `x` to the nearest whole number, with halves rounded away from zero.
+    Round,
+    /// `abs(x)`, the absolute value of `x`.
+    Abs,
+    /// `min(x, y, ...)`, the lowest of its arguments.
+    Min,
+    /// `max(x, y, ...)`, the highest of its arguments.
+    Max,
+}
+
+impl Expr {
+    pub(crate) fn new(root: Node, source: &str, extra_dice: u64, warnings: Vec<Warning>) -> Self {
+        Expr { root, source: source.to_string(), extra_dice, warnings }
+    }
+
+    /// The syntax the expression was parsed from.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// The limits the expression went over when it was parsed, which were clamped rather than failing.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    fn eval_from(&self, source: &mut dyn DieSource) -> Result<i64, Error> {
+        self.root.eval(source, &mut self.extra_dice.clone())
+    }
+
+    fn roll_from(&self, source: &mut dyn DieSource) -> Result<RollResult, Error> {
+        self.root.roll(source, &mut self.extra_dice.clone())
+    }
+
+    /// Rolls all of the dice in the expression, returning the total.
+    ///
+    /// Each call makes a fresh roll. An error is returned if the roll divides by zero or overflows.
+    pub fn eval(&self) -> Result<i64, Error> {
+        self.eval_with(&mut thread_rng())
+    }
+
+    /// Rolls all of the dice in the expression using `rng`, returning the total. Rolls made with
+    /// identically seeded random number generators will always give the same total.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate rand;
+    /// extern crate rouler;
+    ///
+    /// use rand::{rngs::StdRng, SeedableRng};
+    /// use rouler::parse;
+    ///
+    /// # fn main() {
+    /// let expr = parse("3d6").unwrap();
+    /// let first = expr.eval_with(&mut StdRng::seed_from_u64(42));
+    /// let second = expr.eval_with(&mut StdRng::seed_from_u64(42));
+    ///
+    /// assert_eq!(first, second);
+    /// # }
+    /// ```
+    pub fn eval_with<R: Rng + ?Sized>(&self, mut rng: &mut R) -> Result<i64, Error> {
+        self.eval_from(&mut rng)
+    }
+
+    /// Rolls all of the dice in the expression, returning a breakdown of the roll, including the
+    /// value of every die.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rouler::{parse, RollResult};
+    ///
+    /// match parse("4d1dl1").unwrap().roll().unwrap() {
+    ///     RollResult::Dice(dice) => {
+    ///         assert_eq!(dice.total, 3);
+    ///         assert_eq!(dice.dice.iter().filter(|die| die.dropped).count(), 1);
+    ///     },
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    pub fn roll(&self) -> Result<RollResult, Error> {
+        self.roll_with(&mut thread_rng())
+    }
+
+    /// Rolls all of the dice in the expression using `rng`, returning a breakdown of the roll.
+    pub fn roll_with<R: Rng + ?Sized>(&self, mut rng: &mut R) -> Result<RollResult, Error> {
+        self.roll_from(&mut rng)
+    }
+
+    /// Replaces every variable in the expression, such as `@str_mod` or `@{dex}`, with its value in `vars`,
+    /// giving an expression that can be rolled or analysed like any other.
+    ///
+    /// An expression with variables must be bound before it can be rolled, otherwise rolling it fails with
+    /// `Error::UnknownVariable`, as does binding it if `vars` has no value for one of its variables.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use rouler::parse;
+    ///
+    /// let attack = parse("1d20 + @str_mod + @{prof}").unwrap();
+    /// let fighter = vec![("str_mod", 3), ("prof", 2)].into_iter().collect::<HashMap<&str, i64>>();
+    ///
+    /// assert_eq!(attack.bind(&fighter).unwrap().min(), Ok(6));
+    /// assert!(attack.bind(&HashMap::<&str, i64>::new()).is_err());
+    /// ```
+    pub fn bind<V: Variables + ?Sized>(&self, vars: &V) -> Result<Expr, Error> {
+        Ok(Expr { root: self.root.bind(vars)?, ..self.clone() })
+    }
+
+    /// Rolls the expression, as with `eval`, taking the value of each of its variables from `vars`.
+    pub fn eval_in<V: Variables + ?Sized>(&self, vars: &V) -> Result<i64, Error> {
+        self.bind(vars)?.eval()
+    }
+
+    /// Rolls the expression, as with `roll`, taking the value of each of its variables from `vars`.
+    pub fn roll_in<V: Variables + ?Sized>(&self, vars: &V) -> Result<RollResult, Error> {
+        self.bind(vars)?.roll()
+    }
+
+    /// Rolls the expression using `rng`, as with `roll_with`, and also returns a log of every die drawn,
+    /// which can be given to `replay` to repeat the roll exactly.
+    pub fn roll_recorded<R: Rng + ?Sized>(&self, mut rng: &mut R) -> Result<(RollResult, RollLog), Error> {
+        let mut recorder = Recorder::new(&mut rng);
+        let result = self.roll_from(&mut recorder)?;
+        Ok((result, recorder.finish(&self.source)))
+    }
+
+    /// Rolls the expression again using the dice drawn in a recorded `log`, giving the same breakdown as
+    /// the roll that was recorded.
+    ///
+    /// An error is returned if the log doesn't match the expression, such as if it was recorded from a
+    /// different expression or has been altered.
+    pub fn replay(&self, log: &RollLog) -> Result<RollResult, Error> {
+        let mut replayer = Replayer::new(log);
+        let result = self.roll_from(&mut replayer);
+        replayer.finish()?;
+        result
+    }
+
+    /// Works out the exact chance of every total the expression can produce, without rolling it.
+    ///
+    /// An error is returned if any possible roll would fail, such as by dividing by zero, or if there are
+    /// too many possible totals or steps to work out.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rouler::parse;
+    ///
+    /// let attack = parse("1d20 + 5").unwrap().distribution().unwrap();
+    ///
+    /// assert!((attack.probability(25) - 0.05).abs() < 1e-12);
+    /// assert_eq!(attack.probability(26), 0.0);
+    /// ```
+    pub fn distribution(&self) -> Result<Distribution, Error> {
+        self.root.distribution()
+    }
+
+    /// The chance of the expression's total comparing to `threshold` in the given way, worked out from its
+    /// distribution.
+    pub fn chance(&self, comparison: Comparison, threshold: i64) -> Result<f64, Error> {
+        Ok(self.distribution()?.chance(comparison, threshold))
+    }
+
+    /// Works out the chances of this expression's total beating, tying with and losing to that of `other`.
+    pub fn against(&self, other: &Expr) -> Result<Odds, Error> {
+        Ok(self.distribution()?.against(&other.distribution()?))
+    }
+
+    /// Rolls the expression `samples` times, using random numbers generated from `seed`, and collects
+    /// the totals into a `Histogram`. The same seed always gives the same histogram.
+    ///
+    /// An error is returned if any of the rolls fails.
+    pub fn simulate(&self, samples: u64, seed: u64) -> Result<Histogram, Error> {
+        Histogram::simulate(self, samples, seed)
+    }
+
+    /// The average total of the expression.
+    ///
+    /// Where the total is a sum or product of dice that are neither kept nor dropped, the mean is worked out
+    /// directly from the dice, and otherwise from the expression's distribution.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rouler::parse;
+    ///
+    /// assert_eq!(parse("2d6 + 3").unwrap().mean(), Ok(10.0));
+    /// assert!((parse("1000d1000").unwrap().mean().unwrap() - 500_500.0).abs() < 1e-6);
+    /// ```
+    pub fn mean(&self) -> Result<f64, Error> {
+        match self.moments()? {
+            Some((mean, _)) => Ok(mean),
+            None => Ok(self.distribution()?.mean()),
+        }
+    }
+
+    /// The standard deviation of the expression's total, worked out as for `mean`.
+    pub fn std_dev(&self) -> Result<f64, Error> {
+        match self.moments()? {
+            Some((_, variance)) => Ok(variance.sqrt()),
+            None => Ok(self.distribution()?.std_dev()),
+        }
+    }
+
+    /// The mean and variance of the total worked out directly from the dice, if they can be. As with the
+    /// distribution, an error is returned if any total would overflow.
+    fn moments(&self) -> Result<Option<(f64, f64)>, Error> {
+        let moments = self.root.moments()?;
+        if moments.is_some() {
+            self.root.bounds()?;
+        }
+        Ok(moments)
+    }
+
+    /// The lowest total the expression can produce.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rouler::parse;
+    ///
+    /// let damage = parse("2d6 - 1d4").unwrap();
+    ///
+    /// assert_eq!(damage.min(), Ok(-2));
+    /// assert_eq!(damage.max(), Ok(11));
+    /// ```
+    pub fn min(&self) -> Result<i64, Error> {
+        self.root.bounds().map(|(min, _)| min)
+    }
+
+    /// The highest total the expression can produce.
+    ///
+    /// Exploding dice are followed for as long as the expression's limits allow, so this can be far above
+    /// any total its distribution gives a real chance of, as for `1d6!`, which can reach 6000.
+    pub fn max(&self) -> Result<i64, Error> {
+        self.root.bounds().map(|(_, max)| max)
+    }
+
+    /// The lowest total that at least `p` percent of rolls are no higher than, worked out from the
+    /// expression's distribution. See `Distribution::percentile`.
+    pub fn percentile(&self, p: f64) -> Result<i64, Error> {
+        Ok(self.distribution()?.percentile(p))
+    }
+}
+
+/// Parses an expression with the default limits, as [`parse`](fn.parse.html) does.
+impl FromStr for Expr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        parse_expr(s, &Limits::default())
+    }
+}
+
+impl Node {
+    fn bind<V: Variables + ?Sized>(&self, vars: &V) -> Result<Node, Error> {
+        match *self {
+            Node::Variable(ref name) => vars.get(name)
+                .map(Node::Number)
+                .ok_or_else(|| Error::UnknownVariable { name: name.clone() }),
+            Node::Negation(ref operand) => Ok(Node::Negation(Box::new(operand.bind(vars)?))),
+            Node::Operation(op, ref lhs, ref rhs) => Ok(Node::Operation(op, Box::new(lhs.bind(vars)?), Box::new(rhs.bind(vars)?))),
+            Node::Call(function, ref args) => Ok(Node::Call(function, args.iter().map(|arg| arg.bind(vars)).collect::<Result<_, _>>()?)),
+            _ => Ok(self.clone()),
+        }
+    }
+
+    fn eval(&self, source: &mut dyn DieSource, extra: &mut u64) -> Result<i64, Error> {
+        match *self {
+            Node::Number(number) => Ok(number),
+            Node::Roll(ref roll) => roll.roll_dice(source, extra),
+            Node::Variable(ref name) => Err(Error::UnknownVariable { name: name.clone() }),
+            Node::Negation(ref operand) => negate(operand.eval(source, extra)?),
+            Node::Operation(op, ref lhs, ref rhs) => op.apply(lhs.eval(source, extra)?, rhs.eval(source, extra)?),
+            Node::Call(function, ref args) if function.rounds() => function.round(exact(&args[0].roll(source, extra)?)?),
+            Node::Call(function, ref args) => {
+                function.apply(&args.iter().map(|arg| arg.eval(source, extra)).collect::<Result<Vec<i64>, Error>>()?)
+            },
+        }
+    }
+
+    fn roll(&self, source: &mut dyn DieSource, extra: &mut u64) -> Result<RollResult, Error> {
+        match *self {
+            Node::Number(number) => Ok(RollResult::Number(number)),
+            Node::Roll(ref roll) => Ok(RollResult::Dice(roll.roll(source, extra)?)),
+            Node::Variable(ref name) => Err(Error::UnknownVariable { name: name.clone() }),
+            Node::Negation(ref operand) => {
+                let operand = operand.roll(source, extra)?;
+                Ok(RollResult::Negation { total: negate(operand.total())?, operand: Box::new(operand) })
+            },
+            Node::Operation(op, ref lhs, ref rhs) => {
+                let (lhs, rhs) = (lhs.roll(source, extra)?, rhs.roll(source, extra)?);
+                Ok(RollResult::Operation {
+                    op,
+                    total: op.apply(lhs.total(), rhs.total())?,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                })
+            },
+            Node::Call(function, ref args) => {
+                let args = args.iter().map(|arg| arg.roll(source, extra)).collect::<Result<Vec<RollResult>, Error>>()?;
+                let total = if function.rounds() {
+                    function.round(exact(&args[0])?)?
+                } else {
+                    function.apply(&args.iter().map(RollResult::total).collect::<Vec<i64>>())?
+                };
+                Ok(RollResult::Call { function, args, total })
+            },
+        }
+    }
+
+    fn distribution(&self) -> Result<Distribution, Error> {
+        match *self {
+            Node::Number(number) => Ok(Distribution::constant(number)),
+            Node::Roll(ref roll) => roll.distribution(),
+            Node::Variable(ref name) => Err(Error::UnknownVariable { name: name.clone() }),
+            Node::Negation(ref operand) => operand.distribution()?.map(negate),
+            Node::Operation(op, ref lhs, ref rhs) => lhs.distribution()?.combine(op, &rhs.distribution()?),
+            Node::Call(function, ref args) if function.rounds() => {
+                let outcomes = args[0].exact_outcomes()?.into_iter()
+                    .map(|(value, probability)| Ok((function.round(value)?, probability)))
+                    .collect::<Result<Vec<(i64, f64)>, Error>>()?;
+                Distribution::from_outcomes(outcomes)
+            },
+            Node::Call(function, ref args) => {
+                let mut distribution = args[0].distribution()?.map(|value| function.apply(&[value]))?;
+                for arg in &args[1..] {
+                    distribution = distribution.pairwise(&arg.distribution()?, |lhs, rhs| function.apply(&[lhs, rhs]))?;
+                }
+                Ok(distribution)
+            },
+        }
+    }
+
+    /// The mean and variance of the node's total, or `None` if they can only be found from its distribution.
+    fn moments(&self) -> Result<Option<(f64, f64)>, Error> {
+        Ok(match *self {
+            Node::Number(number) => Some((number as f64, 0.0)),
+            Node::Roll(ref roll) => roll.moments()?,
+            Node::Variable(ref name) => return Err(Error::UnknownVariable { name: name.clone() }),
+            Node::Negation(ref operand) => operand.moments()?.map(|(mean, variance)| (-mean, variance)),
+            // Nor can those of functions, except for rounding a whole number, which leaves it as it is
+            Node::Call(function, ref args) if function.rounds() && !args[0].divides() => args[0].moments()?,
+            Node::Call(..) => None,
+            Node::Operation(op, ref lhs, ref rhs) => match (lhs.moments()?, rhs.moments()?) {
+                (Some((lhs_mean, lhs_variance)), Some((rhs_mean, rhs_variance))) => match op {
+                    Op::Add => Some((lhs_mean + rhs_mean, lhs_variance + rhs_variance)),
+                    Op::Subtract => Some((lhs_mean - rhs_mean, lhs_variance + rhs_variance)),
+                    Op::Multiply => {
+                        let (lhs_square, rhs_square) = (lhs_mean * lhs_mean, rhs_mean * rhs_mean);
+                        Some((lhs_mean * rhs_mean, (lhs_variance + lhs_square) * (rhs_variance + rhs_square) - lhs_square * rhs_square))
+                    },
+                    // Integer division rounds, and the other operators aren't linear, so their means can't be
+                    // found from the means of their operands
+                    _ => None,
+                },
+                _ => None,
+            },
+        })
+    }
+
+    /// The lowest and highest totals of the node.
+    fn bounds(&self) -> Result<(i64, i64), Error> {
+        match *self {
+            Node::Number(number) => Ok((number, number)),
+            Node::Roll(ref roll) => roll.bounds(),
+            Node::Variable(ref name) => Err(Error::UnknownVariable { name: name.clone() }),
+            Node::Negation(ref operand) => {
+                let (min, max) = operand.bounds()?;
+                Ok((negate(max)?, negate(min)?))
+            },
+            Node::Operation(op, ref lhs, ref rhs) => {
+                let ((lhs_min, lhs_max), (rhs_min, rhs_max)) = (lhs.bounds()?, rhs.bounds()?);
+                match op {
+                    Op::Add => Ok((op.apply(lhs_min, rhs_min)?, op.apply(lhs_max, rhs_max)?)),
+                    Op::Subtract => Ok((op.apply(lhs_min, rhs_max)?, op.apply(lhs_max, rhs_min)?)),
+                    Op::Divide => quotient_bounds((lhs_min, lhs_max), (rhs_min, rhs_max), rhs, |lhs, rhs| op.apply(lhs, rhs)),
+                    // Products are monotonic in each operand, so the extremes lie at the corners
+                    Op::Multiply => corners((lhs_min, lhs_max), (rhs_min, rhs_max), |lhs, rhs| op.apply(lhs, rhs)),
+                    // Remainders, powers and comparisons aren't monotonic in their operands, so their extremes
+                    // are found from their distributions
+                    Op::Modulo | Op::Power | Op::Compare(_) | Op::And | Op::Or => {
+                        let distribution = self.distribution()?;
+                        Ok((distribution.min(), distribution.max()))
+                    },
+                }
+            },
+            // Rounding is monotonic, so the extremes are the rounded extremes of the exact argument
+            Node::Call(function, ref args) if function.rounds() => {
+                let (min, max) = args[0].exact_bounds()?;
+                Ok((function.round(min)?, function.round(max)?))
+            },
+            Node::Call(function, ref args) => {
+                let (mut min, mut max) = args[0].bounds()?;
+                if function == Function::Abs && min < 0 {
+                    let (low, high) = (min.checked_abs().ok_or(Error::Overflow)?, max.abs());
+                    // a range spanning zero reaches zero, but otherwise its ends swap places
+                    return Ok(if max >= 0 { (0, low.max(high)) } else { (high, low) });
+                }
+                for arg in &args[1..] {
+                    let (arg_min, arg_max) = arg.bounds()?;
+                    // the lowest and highest are monotonic in each argument
+                    min = function.apply(&[min, arg_min])?;
+                    max = function.apply(&[max, arg_max])?;
+                }
+                Ok((min, max))
+            },
+        }
+    }
+
+    /// Whether the node divides exactly within a rounding function's argument, so that its value there
+    /// needn't be whole.
+    fn divides(&self) -> bool {
+        match *self {
+            Node::Negation(ref operand) => operand.divides(),
+            Node::Operation(Op::Divide, ..) => true,
+            Node::Operation(op, ref lhs, ref rhs) if op.is_exact() => lhs.divides() || rhs.divides(),
+            _ => false,
+        }
+    }
+
+    /// The chance of each exact value of the node within a rounding function's argument.
+    fn exact_outcomes(&self) -> Result<Vec<(Ratio, f64)>, Error> {
+        match *self {
+            Node::Negation(ref operand) => {
+                let mut outcomes = operand.exact_outcomes()?.into_iter()
+                    .map(|(value, probability)| Ok((value.negate()?, probability)))
+                    .collect::<Result<Vec<(Ratio, f64)>, Error>>()?;
+                outcomes.reverse();
+                Ok(outcomes)
+            },
+            Node::Operation(op, ref lhs, ref rhs) if op.is_exact() => {
+                distribution::pairwise(&lhs.exact_outcomes()?, &rhs.exact_outcomes()?, |lhs, rhs| lhs.apply(op, rhs))
+            },
+            _ => Ok(self.distribution()?.iter().map(|(value, probability)| (Ratio::from(value), probability)).collect()),
+        }
+    }
+
+    /// The lowest and highest exact values of the node within a rounding function's argument.
+    fn exact_bounds(&self) -> Result<(Ratio, Ratio), Error> {
+        match *self {
+            Node::Negation(ref operand) => {
+                let (min, max) = operand.exact_bounds()?;
+                Ok((max.negate()?, min.negate()?))
+            },
+            Node::Operation(op, ref lhs, ref rhs) if op.is_exact() => {
+                let ((lhs_min, lhs_max), (rhs_min, rhs_max)) = (lhs.exact_bounds()?, rhs.exact_bounds()?);
+                match op {
+                    Op::Add => Ok((lhs_min.apply(op, rhs_min)?, lhs_max.apply(op, rhs_max)?)),
+                    Op::Subtract => Ok((lhs_min.apply(op, rhs_max)?, lhs_max.apply(op, rhs_min)?)),
+                    // A denominator which can fall either side of zero needn't ever be zero once it isn't
+                    // whole, but the quotient isn't monotonic in it, so the extremes are found by trying each
+                    Op::Divide if rhs_min <= Ratio::from(0) && Ratio::from(0) <= rhs_max => {
+                        let outcomes = self.exact_outcomes()?;
+                        Ok((outcomes[0].0, outcomes[outcomes.len() - 1].0))
+                    },
+                    _ => corners((lhs_min, lhs_max), (rhs_min, rhs_max), |lhs, rhs| lhs.apply(op, rhs)),
+                }
+            },
+            _ => {
+                let (min, max) = self.bounds()?;
+                Ok((Ratio::from(min), Ratio::from(max)))
+            },
+        }
+    }
+}
+
+/// The exact value of a rolled argument to a rounding function.
+fn exact(result: &RollResult) -> Result<Ratio, Error> {
+    match *result {
+        RollResult::Negation { ref operand, .. } => exact(operand)?.negate(),
+        RollResult::Operation { op, ref lhs, ref rhs, .. } if op.is_exact() => exact(lhs)?.apply(op, exact(rhs)?),
+        _ => Ok(Ratio::from(result.total())),
+    }
+}
+
+/// An exact value within a rounding function's argument, which needn't be whole. It is kept in lowest
+/// terms with a positive denominator, so that equal values are always stored alike.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Ratio {
+    numerator: i64,
+    denominator: i64,
+}
+
+impl Ratio {
+    fn new(numerator: i128, denominator: i128) -> Result<Self, Error> {
+        if denominator == 0 {
+            return Err(Error::DivisionByZero);
+        }
+        let (mut a, mut b) = (numerator.abs(), denominator.abs());
+        while b != 0 {
+            let remainder = a % b;
+            a = b;
+            b = remainder;
+        }
+        let divisor = a * denominator.signum();
+        Ok(Ratio {
+            numerator: i64::try_from(numerator / divisor).map_err(|_| Error::Overflow)?,
+            denominator: i64::try_from(denominator / divisor).map_err(|_| Error::Overflow)?,
+        })
+    }
+
+    fn negate(self) -> Result<Self, Error> {
+        Ratio::new(-i128::from(self.numerator), i128::from(self.denominator))
+    }
+
+    /// Applies one of the operators worked out exactly.
+    fn apply(self, op: Op, other: Ratio) -> Result<Self, Error> {
+        let (a, b) = (i128::from(self.numerator), i128::from(self.denominator));
+        let (c, d) = (i128::from(other.numerator), i128::from(other.denominator));
+        match op {
+            Op::Add => Ratio::new((a * d).checked_add(c * b).ok_or(Error::Overflow)?, b * d),
+            Op::Subtract => Ratio::new((a * d).checked_sub(c * b).ok_or(Error::Overflow)?, b * d),
+            Op::Multiply => Ratio::new(a * c, b * d),
+            Op::Divide => Ratio::new(a * d, b * c),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl From<i64> for Ratio {
+    fn from(value: i64) -> Self {
+        Ratio { numerator: value, denominator: 1 }
+    }
+}
+
+impl Ord for Ratio {
+    fn cmp(&self, other: &Ratio) -> Ordering {
+        (i128::from(self.numerator) * i128::from(other.denominator))
+            .cmp(&(i128::from(other.numerator) * i128::from(self.denominator)))
+    }
+}
+
+impl PartialOrd for Ratio {
+    fn partial_cmp(&self, other: &Ratio) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The lowest and highest results of `f` over the ranges of its operands, where `f` is monotonic in each.
+fn corners<T, F>(lhs: (T, T), rhs: (T, T), f: F) -> Result<(T, T), Error>
+    where T: Ord + Copy, F: Fn(T, T) -> Result<T, Error>
+{
+    let corners = [f(lhs.0, rhs.0)?, f(lhs.0, rhs.1)?, f(lhs.1, rhs.0)?, f(lhs.1, rhs.1)?];
+    Ok((*corners.iter().min().unwrap(), *corners.iter().max().unwrap()))
+}
+
+/// The lowest and highest results of the division `f` over the range of its numerator and the totals of its
+/// denominator `rhs`, which range over `rhs_bounds`. Quotients are monotonic in each operand so long as the denominator keeps to one side of
+/// zero, so a denominator which can fall either side is split into its negative and positive totals.
+fn quotient_bounds<F>(lhs: (i64, i64), rhs_bounds: (i64, i64), rhs: &Node, f: F) -> Result<(i64, i64), Error>
+    where F: Fn(i64, i64) -> Result<i64, Error>
+{
+    let (rhs_min, rhs_max) = rhs_bounds;
+    if rhs_min > 0 || rhs_max < 0 {
+        return corners(lhs, (rhs_min, rhs_max), f);
+    }
+
+    let denominators = rhs.distribution()?;
+    if denominators.probability(0) > 0.0 {
+        return Err(Error::DivisionByZero);
+    }
+    let mut bounds: Option<(i64, i64)> = None;
+    for side in &[denominators.restrict(|value| value < 0), denominators.restrict(|value| value > 0)] {
+        if side.is_empty() {
+            continue;
+        }
+        let (min, max) = corners(lhs, (side.min(), side.max()), &f)?;
+        bounds = Some(bounds.map_or((min, max), |(low, high)| (low.min(min), high.max(max))));
+    }
+    // a denominator with no possible totals leaves nothing to divide by
+    bounds.ok_or(Error::DivisionByZero)
+}
+
+impl Function {
+    /// Whether the function rounds its argument, which is then worked out exactly.
+    fn rounds(self) -> bool {
+        match self {
+            Function::Floor | Function::Ceil | Function::Round => true,
+            Function::Abs | Function::Min | Function::Max => false,
+        }
+    }
+
+    /// Applies the function to the values of its arguments.
+    fn apply(self, args: &[i64]) -> Result<i64, Error> {
+        match self {
+            Function::Floor | Function::Ceil | Function::Round => Ok(args[0]),
+            Function::Abs => args[0].checked_abs().ok_or(Error::Overflow),
+            Function::Min => Ok(*args.iter().min().unwrap()),
+            Function::Max => Ok(*args.iter().max().unwrap()),
+        }
+    }
+
+    /// Rounds an exact value as the function does.
+    fn round(self, value: Ratio) -> Result<i64, Error> {
+        let (numerator, denominator) = (value.numerator, value.denominator);
+        let (quotient, remainder) = (numerator.div_euclid(denominator), numerator.rem_euclid(denominator));
+        // `quotient` is rounded down, as the denominator is positive
+        Ok(match self {
+            Function::Ceil if remainder != 0 => quotient + 1,
+            Function::Round if 2 * i128::from(remainder) > i128::from(denominator)
+                || (2 * i128::from(remainder) == i128::from(denominator) && numerator > 0) => quotient + 1,
+            _ => quotient,
+        })
+    }
+}
+
+fn negate(value: i64) -> Result<i64, Error> {
+    value.checked_neg().ok_or(Error::Overflow)
+}
+
+impl Op {
+    /// Whether the operator is worked out exactly within a rounding function's argument.
+    fn is_exact(self) -> bool {
+        matches!(self, Op::Add | Op::Subtract | Op::Multiply | Op::Divide)
+    }
+
+    pub(crate) fn apply(self, lhs: i64, rhs: i64) -> Result<i64, Error> {
+        let result = match self {
+            Op::Add => lhs.checked_add(rhs),
+            Op::Subtract => lhs.checked_sub(rhs),
+            Op::Multiply => lhs.checked_mul(rhs),
+            Op::Divide | Op::Modulo if rhs == 0 => return Err(Error::DivisionByZero),
+            Op::Divide => lhs.checked_div(rhs),
+            Op::Modulo => lhs.checked_rem_euclid(rhs),
+            Op::Power => return power(lhs, rhs),
+            Op::Compare(comparison) => Some(i64::from(comparison.matches(lhs, rhs))),
+            Op::And => Some(i64::from(lhs != 0 && rhs != 0)),
+            Op::Or => Some(i64::from(lhs != 0 || rhs != 0)),
+        };
+        result.ok_or(Error::Overflow)
+    }
+}
+
+fn power(base: i64, exponent: i64) -> Result<i64, Error> {
+    match base {
+        // the only bases whose powers stay small, however large the exponent
+        0 if exponent < 0 => Err(Error::DivisionByZero),
+        0 => Ok(if exponent == 0 { 1 } else { 0 }),
+        1 => Ok(1),
+        -1 => Ok(if exponent % 2 == 0 { 1 } else { -1 }),
+        // any other base's reciprocal rounds to zero
+        _ if exponent < 0 => Ok(0),
+        _ => u32::try_from(exponent).ok().and_then(|exponent| base.checked_pow(exponent)).ok_or(Error::Overflow),
+    }
+}
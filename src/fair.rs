@@ -0,0 +1,149 @@
+// rouler - A container-based system for generating die rolls
+// Copyright (C) 2016 by John Berry
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::fmt;
+
+use rand::{rngs::OsRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
+
+use error::Error;
+use expr::Expr;
+use limits::Limits;
+use parse::parse_expr;
+use result::RollResult;
+
+/// Separates the random numbers of fair rolls from any other use of the same seed.
+const DOMAIN: &[u8] = b"rouler fair roll v1";
+
+/// A secret seed for provably fair rolls, which lets players check that a roll wasn't chosen by whoever
+/// made it.
+///
+/// Before the roll, the roller publishes the seed's `commitment`, a hash which reveals nothing about the
+/// seed. A player then picks a nonce, and the roll is made from the seed, the nonce and the expression
+/// with `Expr::roll_fair`. Once the seed is revealed, anyone can check the roll with [`verify`](fn.verify.html):
+/// the roller couldn't have changed the seed after committing to it, and couldn't have known the nonce when
+/// choosing it.
+///
+/// # Examples
+///
+/// ```
+/// use rouler::{parse, verify, Seed};
+///
+/// // the server commits to a seed before the roll
+/// let seed = Seed::generate();
+/// let commitment = seed.commitment();
+///
+/// // the player chooses a nonce, and the server rolls
+/// let result = parse("1d20 + 5").unwrap().roll_fair(&seed, "player nonce").unwrap();
+///
+/// // after the seed is revealed, anyone can check the roll
+/// let revealed = seed.to_hex();
+/// let seed = Seed::from_hex(&revealed).unwrap();
+/// assert_eq!(verify("1d20 + 5", &commitment, &seed, "player nonce", &result), Ok(true));
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct Seed([u8; 32]);
+
+impl Seed {
+    /// Generates a new seed from the operating system's source of randomness.
+    pub fn generate() -> Self {
+        let mut bytes = [0; 32];
+        OsRng.fill_bytes(&mut bytes);
+        Seed(bytes)
+    }
+
+    /// Makes a seed from its bytes.
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Seed(bytes)
+    }
+
+    /// Reads a revealed seed written as 64 hexadecimal digits, as given by `to_hex`.
+    pub fn from_hex(hex: &str) -> Result<Self, Error> {
+        let digits = hex.as_bytes();
+        // `from_str_radix` would also take a leading sign, so each digit is checked first
+        if digits.len() != 64 || !digits.iter().all(u8::is_ascii_hexdigit) {
+            return Err(Error::InvalidSeed);
+        }
+
+        let mut bytes = [0; 32];
+        for (byte, pair) in bytes.iter_mut().zip(digits.chunks(2)) {
+            let pair = std::str::from_utf8(pair).map_err(|_| Error::InvalidSeed)?;
+            *byte = u8::from_str_radix(pair, 16).map_err(|_| Error::InvalidSeed)?;
+        }
+        Ok(Seed(bytes))
+    }
+
+    /// The seed's bytes.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// The seed written as 64 lowercase hexadecimal digits, for revealing it once the roll has been made.
+    pub fn to_hex(&self) -> String {
+        to_hex(&self.0)
+    }
+
+    /// The SHA-256 hash of the seed as 64 lowercase hexadecimal digits, for publishing before the roll.
+    pub fn commitment(&self) -> String {
+        to_hex(&Sha256::digest(self.0))
+    }
+
+    /// Makes the random number generator for a roll of `expression` with the player's `nonce`.
+    fn rng(&self, nonce: &str, expression: &str) -> ChaCha20Rng {
+        let mut hasher = Sha256::new();
+        hasher.update(DOMAIN);
+        hasher.update(self.0);
+        // the length keeps the nonce and the expression apart
+        hasher.update((nonce.len() as u64).to_le_bytes());
+        hasher.update(nonce.as_bytes());
+        hasher.update(expression.as_bytes());
+        ChaCha20Rng::from_seed(hasher.finalize().into())
+    }
+}
+
+/// Hides the seed, so that it isn't revealed by accident in logs.
+impl fmt::Debug for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Seed({}...)", &self.commitment()[..8])
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+impl Expr {
+    /// Rolls the expression from a committed `seed` and a `nonce` chosen by the player, so that the roll
+    /// can be checked with [`verify`](fn.verify.html) once the seed is revealed.
+    ///
+    /// The roll depends on the expression exactly as it was written, so it must be verified with the same
+    /// string. It also depends on how this version of rouler draws dice, and on the stream of numbers given
+    /// by the versions of `rand` and `rand_chacha` it is built with, so a roll is only certain to be
+    /// reproduced by the same version of rouler built against the same versions of those crates.
+    pub fn roll_fair(&self, seed: &Seed, nonce: &str) -> Result<RollResult, Error> {
+        self.roll_with(&mut seed.rng(nonce, self.as_str()))
+    }
+}
+
+/// Checks a provably fair roll made by `Expr::roll_fair`, once its seed has been revealed.
+///
+/// Returns `Ok(true)` if `seed` matches the `commitment` published before the roll, and rolling `expression`
+/// from the seed and `nonce` gives exactly `result`. An error is returned if the expression can't be rolled.
+///
+/// The expression is parsed with the default `Limits`. A roll made within other limits can be checked by
+/// parsing it with them and comparing the result of `Expr::roll_fair`.
+///
+/// A roll can only be checked by the same version of rouler that made it, built against the same versions
+/// of `rand` and `rand_chacha`, since a change to how dice are drawn, or to the random number stream, would
+/// roll differently from the same seed.
+pub fn verify(expression: &str, commitment: &str, seed: &Seed, nonce: &str, result: &RollResult) -> Result<bool, Error> {
+    if !seed.commitment().eq_ignore_ascii_case(commitment.trim()) {
+        return Ok(false);
+    }
+    Ok(parse_expr(expression, &Limits::default())?.roll_fair(seed, nonce)? == *result)
+}
@@ -58,8 +58,157 @@
 //! and `s` cannot be a negative number. `n` is allowed to be negative, but rather than rolling "negative dice",
 //! this merely negates the value of the entire roll, such that `-3d6` would generate a value between -3 and -18.
 //!
+//! ### Operators
+//!
+//! Alongside `+`, `-`, `*` and `/`, which rounds toward zero, `%` gives the remainder of a division, which is
+//! never negative, so that `(@hour + 5) % 12` tells the time on a clock. `**` or `^` raises a number to a power,
+//! and groups to the right, so that `2^3^2` is 2<sup>9</sup>. Powers are worked out before `*`, `/` and `%`,
+//! which are worked out before `+` and `-`.
+//!
+//! A `-` in front of any term negates it, as in `-(1d4)` or `1d20 + -1d4`. Powers are worked out first, so
+//! `-2^2` is -4 while `(-2)^2` is 4, and each `-` counts as a level of nesting toward `Limits::depth`. Any
+//! result too large for an `i64` is an error rather than wrapping around.
+//!
+//! ### Comparisons
+//!
+//! Whole totals can be compared with `>`, `>=`, `<`, `<=` or `==`, as in `1d20 + 5 >= 15` or `2d6 + @mod > 9`,
+//! and comparisons can be joined with `and` and `or`. They are worked out after all arithmetic, with `and`
+//! before `or`. A comparison totals 1 if it succeeds and 0 if it fails, while `RollResult::success` and
+//! `RollResult::margin` tell how it went:
+//!
+//! ```
+//! let attack = rouler::parse("1d20 + 5 >= 15").unwrap().roll().unwrap();
+//!
+//! match (attack.success(), attack.margin()) {
+//!     (Some(true), Some(margin)) => println!("HIT by {}", margin),
+//!     (_, Some(margin)) => println!("MISS by {}", -margin),
+//!     _ => unreachable!(),
+//! }
+//! ```
+//!
+//! A comparison written directly after a die code, with no space, is a success target instead, so `2d6>4`
+//! counts the dice over 4 while `2d6 > 4` compares their total. As the two are easily mixed up, a success
+//! target standing where a comparison could have been meant, as the whole roll or beside `and` or `or`,
+//! is noted by `Warning::SuccessCount` in `Expr::warnings`.
+//!
+//! ### Custom dice
+//!
+//! In place of a number of sides, a die may list its faces explicitly, as in `4d[1, 3, 5, 7]` or `2d[-1, 0, 1]`.
+//! Custom dice accept all of the modifiers below, with the highest listed face counting as the die's maximum.
+//!
+//! ### Fate and percentile dice
+//!
+//! `dF` rolls a Fate (or Fudge) die, with faces of -1, 0 and +1, so that `4dF` gives a result between -4 and 4.
+//! `d%` is shorthand for a d100.
+//!
+//! ### Keeping and dropping dice
+//!
+//! A roll may keep or drop a number of its dice, after any explosions and rerolls: `4d6kh3` (or `4d6k3`) keeps
+//! the highest three, `2d20kl1` keeps the lowest one, `4d6dl1` (or `4d6d1`) drops the lowest one, and `4d6dh1`
+//! drops the highest one.
+//!
+//! ### Exploding dice
+//!
+//! A die code may be followed by one of three explosion modifiers, which roll additional dice whenever a
+//! die shows its highest face:
+//!
+//! * `4d6!` - exploding: each explosion adds another die to the roll
+//! * `2d6!!` - compounding: explosions are added onto the die that exploded, rather than as new dice
+//! * `1d8!p` - penetrating: as exploding, but each additional die is worth one less than it rolled
+//!
+//! The face that triggers an explosion can be changed with a compare point, eg. `3d10!>8` explodes on 9 or 10,
+//! and `1d6!5` explodes only on a 5. Because the compare point is greedy, a success target following an explosion
+//! needs an explicit compare point of its own: `4d6!6>=5`.
+//!
+//! Every explosion counts toward the limit of 1000 dice per roll, so rolls such as `1d1!` always terminate.
+//! The limits can be changed with `parse_with_limits`.
+//!
+//! ### Rerolls
+//!
+//! `r` rerolls a die for as long as it matches a compare point, and `ro` rerolls it at most once. The compare
+//! point uses the same comparisons as success targets, and a bare number means an exact match: `4d6r1` rerolls
+//! ones, `2d20ro<3` rerolls a single 1 or 2, and `1d10r<=2` never keeps a 1 or 2. Rerolls can be combined with
+//! each other and with explosions, as in `4d6r1r2` or `1d6r1!`, and count toward the same 1000 dice limit.
+//!
+//! ### Variables
+//!
+//! `@name` or `@{name}` stands for a number supplied when the roll is made, so that `1d20 + @str_mod + @{prof}`
+//! can be parsed once and rolled for any character. Names start with a letter or underscore, followed by any
+//! letters, digits or underscores. The values are given by a map or any other implementation of `Variables`,
+//! through `Expr::bind`, `Expr::eval_in` or `Expr::roll_in`, or to a `Roller` through `Roller::new_in` and
+//! `Roller::reroll_in`.
+//!
+//! ### Functions
+//!
+//! `floor`, `ceil` and `round` each take one argument, and round a division within it rather than truncating
+//! it toward zero: `floor(7 / 2)` is 3, `ceil(7 / 2)` is 4 and `round(-7 / 2)` is -4, with halves rounded away
+//! from zero. The argument is worked out exactly through `+`, `-`, `*` and `/`, and only rounded at the end,
+//! so `floor(-7 / 2 + 0)` is -4 and `round(5 / 2 + 1)` is 4. `abs` gives the absolute
+//! value of its argument, while `min` and `max` take one or more arguments separated by commas, as in
+//! `max(1d20, 1d20) + 5`. Function names are case-insensitive.
+//!
+//! ## Probabilities
+//!
+//! Rather than rolling, [`distribution`](fn.distribution.html) works out the exact chance of every total
+//! a roll can produce:
+//!
+//! ```
+//! let damage = rouler::distribution("2d6 + 3").unwrap();
+//!
+//! assert!((damage.probability(10) - 6.0 / 36.0).abs() < 1e-12);
+//! ```
+//!
+//! Distributions, and histograms of simulated rolls, can be exported as a bar chart, CSV or JSON:
+//!
+//! ```
+//! print!("{}", rouler::distribution("2d6").unwrap().chart(40));
+//! // =>  2   2.78% #######
+//! //     3   5.56% #############
+//! //     ...
+//! ```
+//!
+//! An `Expr`, including a Roller's `expr()`, also offers summary statistics such as `mean()` and `percentile(p)`,
+//! which are worked out directly from the dice where possible.
+//!
 //! ## Changelog
 //!
+//! ### Unreleased
+//! * Added exploding (`!`), compounding (`!!`) and penetrating (`!p`) dice
+//! * Added reroll (`r`) and reroll once (`ro`) modifiers
+//! * Added keep lowest (`kl`) and drop highest (`dh`)
+//! * Fixed keep highest (`kh`) keeping the lowest dice
+//! * Custom dice now support keep/drop, success targets, rerolls and explosions
+//! * Success targets may be negative
+//! * Added Fate (`dF`) and percentile (`d%`) dice
+//! * `roll_dice_or_fail` and `roller_or_fail` now return `rouler::Error`
+//! * Division by zero, overflow and out of range numbers are reported as errors rather than panicking
+//! * Added `parse` and `Expr`, for rolling the same dice repeatedly without parsing them again
+//! * Rollers no longer reparse their syntax on every reroll, and are no longer `Copy`
+//! * Added `RollResult`, a breakdown of each roll, from `Expr::roll` and `Roller::result`
+//! * Added plain text, Markdown and ANSI renderings of roll breakdowns, shown by `{:#}` for Rollers
+//! * Added `roll_dice_with`, `Roller::reroll_with`, `Expr::eval_with` and `Expr::roll_with`, for rolling with a
+//!   given random number generator, and `Roller::with_seed` for reproducible rolls
+//! * Added `distribution` and `Expr::distribution`, giving the exact chance of each total of a roll
+//! * Added `mean`, `std_dev`, `min`, `max` and `percentile` to `Expr` and `Distribution`
+//! * Added `chance`, giving the chance that a roll meets a target, and `Comparison`
+//! * Added `opposed` and `Expr::against`, giving the chances of one roll beating, tying with or losing to another
+//! * Added `simulate`, collecting many seeded rolls into a `Histogram`, with batches run across threads by the
+//!   `parallel` feature
+//! * Added ASCII chart, CSV and JSON exports of distributions and histograms
+//! * Added `Expr::roll_recorded`, logging every die drawn in a roll, and `Expr::replay` to repeat a logged roll
+//! * Added provably fair rolls, made by `Expr::roll_fair` from a committed `Seed` and checked by `verify`
+//! * Added `parse_with_limits` and `Limits`, configuring the limits on dice, sides, nesting and explosions, and
+//!   whether going over them clamps the roll with a `Warning` or is an error
+//! * Rollers now own their syntax, so are `'static`, and can be parsed with `FromStr`, as can `Expr`
+//! * Added the `serde` feature, serializing Rollers and `Expr` as their syntax
+//! * Added variables, such as `@str_mod` or `@{dex}`, given values by `Expr::bind`, `Expr::eval_in`,
+//!   `Expr::roll_in`, `Roller::new_in` and `Roller::reroll_in`
+//! * Added the `floor`, `ceil`, `round`, `abs`, `min` and `max` functions, and `RollResult::Call`
+//! * Added `%` and `**` (or `^`), and unary `-` on any term, with `RollResult::Negation`
+//! * Rendered breakdowns keep the parentheses around a product or quotient on the right of `*`, as in `2*(7/2)`
+//! * Added comparisons between whole totals, such as `1d20 + 5 >= 15`, joined by `and` and `or`, with
+//!   `RollResult::success` and `RollResult::margin`, and a warning for success targets that look like them
+//!
 //! ### 0.1.3
 //! * Added Iterator support to Rollers
 //! * Better type inference for Roller::new()
@@ -75,18 +224,91 @@ extern crate pest;
 #[macro_use]
 extern crate pest_derive;
 extern crate rand;
+extern crate rand_chacha;
+#[cfg(feature = "parallel")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+extern crate serde;
+extern crate sha2;
 #[macro_use]
 extern crate lazy_static;
 
+mod distribution;
+mod error;
+mod export;
+mod expr;
+mod fair;
+mod limits;
 mod parse;
+mod record;
+mod render;
+mod result;
 mod roll;
+#[cfg(feature = "serde")]
+mod serialize;
+mod simulate;
+mod variables;
 
 use std::cmp::Ordering;
 use std::fmt;
+use std::str::FromStr;
+
+use rand::{thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
 
 use parse::*;
-use pest::*;
-use pest::error::Error;
+
+pub use distribution::{Distribution, Odds};
+pub use error::{Error, Limit};
+pub use expr::{Expr, Function, Op};
+pub use fair::{verify, Seed};
+pub use limits::{Limits, Policy, Warning};
+pub use record::{Draw, RollLog};
+pub use render::Style;
+pub use result::{DiceResult, Die, RollResult};
+pub use roll::{Comparison, DieType};
+pub use simulate::Histogram;
+pub use variables::Variables;
+
+/// Parses a die roll into an `Expr`, which can then be rolled any number of times without
+/// being parsed again. This is the fastest way to make many rolls of the same dice.
+///
+/// Returns an `Error` if the syntax is incorrect, or if it contains a number too large to be
+/// represented.
+///
+/// # Examples
+/// ```
+/// use rouler::parse;
+///
+/// let stat = parse("4d6kh3").unwrap();
+/// let stats = (0..6).map(|_| stat.eval().unwrap()).collect::<Vec<i64>>();
+///
+/// assert!(parse("4d6kh").is_ok());
+/// assert!(parse("food4").is_err());
+/// ```
+pub fn parse(r: &str) -> Result<Expr, Error> {
+    parse_expr(r, &Limits::default())
+}
+
+/// Parses a die roll as `parse` does, within the given `Limits` rather than the defaults.
+///
+/// Depending on the limits' `Policy`, a roll that goes over them is either clamped, with a warning noted in
+/// `Expr::warnings`, or returns `Error::LimitExceeded`. Rerolls and explosions are checked against the
+/// limits as the expression is rolled, and any that are clamped are noted in `RollResult::warnings`.
+///
+/// # Examples
+/// ```
+/// use rouler::{parse_with_limits, Limits};
+///
+/// let simulator = Limits { dice: 100_000, ..Limits::default() };
+/// let expr = parse_with_limits("50000d6", &simulator).unwrap();
+///
+/// assert_eq!(expr.min(), Ok(50_000));
+/// assert!(expr.warnings().is_empty());
+/// ```
+pub fn parse_with_limits(r: &str, limits: &Limits) -> Result<Expr, Error> {
+    parse_expr(r, limits)
+}
 
 /// A simple function for throwaway die rolls that do not need to be saved as a
 /// `Roller`. Provided for convenience.
@@ -95,7 +317,9 @@ use pest::error::Error;
 ///
 /// # Panics
 ///
-/// As `roll_dice` parses its argument, it will thus panic if the given syntax is incorrect.
+/// As `roll_dice` parses its argument, it will thus panic if the given syntax is incorrect. It will also
+/// panic if the roll itself fails, such as by dividing by zero. Use `roll_dice_or_fail` to handle these
+/// cases instead.
 ///
 /// # Examples
 /// ```
@@ -105,63 +329,210 @@ use pest::error::Error;
 /// ```
 ///
 pub fn roll_dice(r: &str) -> i64 {
-    let parser = RollParser::parse(Rule::calc, r);
-    compute(parser.expect("Failed to parse roll!"))
+    roll_dice_with(r, &mut thread_rng())
+}
+
+/// As `roll_dice`, but rolls the dice using the given random number generator. Using a seeded
+/// generator makes rolls reproducible.
+///
+/// # Panics
+///
+/// Panics if the given syntax is incorrect, or if the roll fails, as with `roll_dice`.
+///
+/// # Examples
+/// ```
+/// extern crate rand;
+/// extern crate rouler;
+///
+/// use rand::{rngs::StdRng, SeedableRng};
+/// use rouler::roll_dice_with;
+///
+/// # fn main() {
+/// let mut rng = StdRng::seed_from_u64(1234);
+/// println!("Fireball: {}", roll_dice_with("8d6", &mut rng));
+/// # }
+/// ```
+pub fn roll_dice_with<R: Rng + ?Sized>(r: &str, rng: &mut R) -> i64 {
+    eval_or_panic(&parse_or_panic(r), rng)
 }
 
 /// A function for throwaway die rolls that do not need to be saved as a
 /// `Roller`. Provided for convenience.
 ///
 /// Takes an input of a `&str` containing syntax for a die roll, returns Ok(total)
-/// if the input parses successfully, otherwise a `ParsingError`.
+/// if the input parses successfully, otherwise an `Error` describing what went wrong.
 ///
 /// # Examples
 /// ```
-/// use rouler::roll_dice_or_fail;
+/// use rouler::{roll_dice_or_fail, Error};
 ///
 /// assert!(roll_dice_or_fail("6d6").is_ok());
-/// assert!(roll_dice_or_fail("food4").is_err())
+/// assert_eq!(roll_dice_or_fail("1d6 / 0"), Err(Error::DivisionByZero));
+///
+/// match roll_dice_or_fail("food4") {
+///     Err(Error::Syntax { span, .. }) => assert_eq!(span.start, 0),
+///     _ => unreachable!(),
+/// }
 /// ```
-pub fn roll_dice_or_fail(r: &str) -> Result<i64, Error<impl RuleType>> {
-    let parser = RollParser::parse(Rule::calc, r);
-    parser.map(|p| compute(p))
+pub fn roll_dice_or_fail(r: &str) -> Result<i64, Error> {
+    parse(r)?.eval()
 }
 
 /// A function for safely creating a new `Roller` without panicking.
 ///
 /// Takes a `&str` input and if the syntax parses, returns a Roller wrapped by a Result.
-/// Otherwise returns a `ParsingError`.
+/// Otherwise returns an `Error` describing what went wrong.
 ///
 /// # Examples
 /// ```
 /// use rouler::roller_or_fail;
 ///
+/// assert!(roller_or_fail("1d20 + 5").is_ok());
 ///
+/// let err = roller_or_fail("/ro 1d20").unwrap_err();
+/// println!("{}", err); // => invalid roll at position 2, expected an expression
 /// ```
-pub fn roller_or_fail<'a>(r: &'a str) -> Result<Roller<'a>, Error<impl RuleType>> {
-    let parser = RollParser::parse(Rule::calc, r);
-    parser.map(|p| {
-        Roller {
-            roll: r,
-            total: compute(p),
-        }
+pub fn roller_or_fail(r: &str) -> Result<Roller, Error> {
+    let expr = parse(r)?;
+    Ok(Roller {
+        result: expr.roll()?,
+        expr,
+        rng: None,
     })
 }
 
+/// Works out the exact chance of every total that the given roll can produce, without rolling any dice.
+///
+/// Sums, keeping and dropping, success counting, custom dice and arithmetic are all accounted for exactly.
+/// Explosions are followed until the chance of another becomes negligible.
+///
+/// # Errors
+///
+/// Returns an `Error` if the syntax is incorrect, if any possible roll would fail, if the roll has too many
+/// possible totals or would take too many steps to work out, or if it both explodes and keeps or drops dice.
+///
+/// # Examples
+/// ```
+/// use rouler::distribution;
+///
+/// let check = distribution("3d6 + 2").unwrap();
+/// let total: f64 = check.iter().map(|(_, probability)| probability).sum();
+///
+/// assert!((total - 1.0).abs() < 1e-12);
+/// assert_eq!(check.probability(4), 0.0);
+/// assert!(check.probability(5) > 0.0);
+/// ```
+pub fn distribution(r: &str) -> Result<Distribution, Error> {
+    parse(r)?.distribution()
+}
+
+/// Works out the exact chance that the given roll's total compares to `threshold` in the given way, such
+/// as the chance of an attack roll meeting a target's armour class.
+///
+/// # Errors
+///
+/// Returns an `Error` in the same cases as [`distribution`](fn.distribution.html).
+///
+/// # Examples
+/// ```
+/// use rouler::{chance, Comparison};
+///
+/// let hit = chance("1d20 + 7", Comparison::GTE, 16).unwrap();
+/// assert!((hit - 0.6).abs() < 1e-12);
+/// ```
+pub fn chance(r: &str, comparison: Comparison, threshold: i64) -> Result<f64, Error> {
+    parse(r)?.chance(comparison, threshold)
+}
+
+/// Works out the exact chances of one roll beating, tying with and losing to another, as in opposed
+/// checks.
+///
+/// # Errors
+///
+/// Returns an `Error` if either roll fails as for [`distribution`](fn.distribution.html).
+///
+/// # Examples
+/// ```
+/// use rouler::opposed;
+///
+/// let odds = opposed("1d20 + 5", "1d20 + 2").unwrap();
+///
+/// assert!(odds.win > odds.lose);
+/// assert!((odds.win + odds.tie + odds.lose - 1.0).abs() < 1e-12);
+/// ```
+pub fn opposed(attacker: &str, defender: &str) -> Result<Odds, Error> {
+    parse(attacker)?.against(&parse(defender)?)
+}
+
+/// Rolls the given roll `samples` times and collects the totals into a `Histogram`, for rolls whose exact
+/// `distribution` is too expensive to work out. The rolls are made with random numbers generated from `seed`,
+/// so the same seed always gives the same histogram.
+///
+/// With the `parallel` feature enabled, the rolls are made in batches across threads.
+///
+/// # Errors
+///
+/// Returns an `Error` if the syntax is incorrect, or if any of the rolls fails.
+///
+/// # Examples
+/// ```
+/// use rouler::simulate;
+///
+/// let histogram = simulate("3d6", 10_000, 42).unwrap();
+///
+/// assert_eq!(histogram.samples(), 10_000);
+/// assert_eq!(histogram, simulate("3d6", 10_000, 42).unwrap());
+/// ```
+pub fn simulate(r: &str, samples: u64, seed: u64) -> Result<Histogram, Error> {
+    parse(r)?.simulate(samples, seed)
+}
+
+fn parse_or_panic(r: &str) -> Expr {
+    parse(r).unwrap_or_else(|err| panic!("Failed to parse roll: {}", err))
+}
+
+fn bind_or_panic<V: Variables + ?Sized>(expr: &Expr, vars: &V) -> Expr {
+    expr.bind(vars).unwrap_or_else(|err| panic!("Failed to roll: {}", err))
+}
+
+fn eval_or_panic<R: Rng + ?Sized>(expr: &Expr, rng: &mut R) -> i64 {
+    expr.eval_with(rng).unwrap_or_else(|err| panic!("Failed to roll: {}", err))
+}
+
+fn roll_or_panic<R: Rng + ?Sized>(expr: &Expr, rng: &mut R) -> RollResult {
+    expr.roll_with(rng).unwrap_or_else(|err| panic!("Failed to roll: {}", err))
+}
+
 /// The `Roller` is the core struct of the library. The basic principle is to provide a reusable
 /// container that provides a specific kind of die roll, so that it can be quickly and easily repeated
 /// whenever called for. Each container contains the syntax of the roll type it represents, and the
-/// value of the last roll it made. Containers are thus self-mutating, but self-contained.
+/// result of the last roll it made. Containers are thus self-mutating, but self-contained.
 ///
 /// The main benefit is thus in code organization, as one can store different types of rolls for
-/// easy use later.
-#[derive(Debug, Clone, Copy)]
-pub struct Roller<'a> {
-    roll: &'a str,
-    total: i64,
+/// easy use later. The syntax is parsed only once, when the `Roller` is created, so rerolling is cheap.
+///
+/// A `Roller` owns a copy of its syntax, so it can be kept in long-lived structs and collections, or sent
+/// between threads. It can also be parsed from a string:
+///
+/// ```
+/// use rouler::Roller;
+///
+/// let damage: Roller = "2d6 + 3".parse().unwrap();
+///
+/// assert!(5 <= damage.total() && damage.total() <= 15);
+/// assert!("food4".parse::<Roller>().is_err());
+/// ```
+///
+/// With the `serde` feature enabled, a `Roller` is serialized as its syntax, and is rolled afresh when it is
+/// deserialized.
+#[derive(Debug, Clone)]
+pub struct Roller {
+    expr: Expr,
+    result: RollResult,
+    rng: Option<ChaCha20Rng>,
 }
 
-impl<'a> Roller<'a> {
+impl Roller {
     /// Creates a new `Roller` with the given die roll syntax, and populates the stored total with a first
     /// roll of the indicated dice. Because subsequent rerolls mutate the object in order to store the most
     /// recent roll, it should be declared with `let mut` if you intend to reroll it.
@@ -169,7 +540,9 @@ impl<'a> Roller<'a> {
     /// # Panics
     ///
     /// As a `Roller` rolls itself on creation, it thus triggers the parser, and incorrect syntax will cause
-    /// a panic.
+    /// a panic, as will a roll that fails to evaluate. Use `roller_or_fail` to handle these cases instead.
+    /// A roll with variables, such as `1d20 + @str_mod`, can't be rolled without their values, so it also
+    /// panics, and should be created with `Roller::new_in`.
     ///
     /// # Examples
     ///
@@ -197,23 +570,95 @@ impl<'a> Roller<'a> {
     /// }
     /// ```
     ///
+    /// To find how likely one roll is to beat another, rather than comparing a single pair of rolls, see
+    /// [`opposed`](fn.opposed.html).
+    ///
     /// For convenience's sake, Rollers also implement `Display`, so they are printable:
     ///
     /// ```
     /// # use rouler::Roller;
     /// println!("{}", Roller::new("4d8 + 5"));
     /// // => [4d8 + 5: 24]
+    /// println!("{:#}", Roller::new("4d8 + 5"));
+    /// // => [4d8 + 5: (7+2+6+4)+5 = 24]
     /// ```
-    pub fn new(roll: &'a str) -> Self {
+    pub fn new(roll: &str) -> Self {
+        let expr = parse_or_panic(roll);
         Roller {
-            roll: roll,
-            total: roll_dice(roll),
+            result: roll_or_panic(&expr, &mut thread_rng()),
+            expr,
+            rng: None,
+        }
+    }
+
+    /// Creates a new `Roller` whose rolls are generated from `seed`. Two Rollers created with the same
+    /// syntax and seed will always make the same sequence of rolls, which makes rolls reproducible for
+    /// replays and testing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the syntax is incorrect or the first roll fails, as with `Roller::new`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rouler::Roller;
+    ///
+    /// let mut first = Roller::with_seed("4d6kh3", 2016);
+    /// let mut second = Roller::with_seed("4d6kh3", 2016);
+    ///
+    /// assert_eq!(first.iter().take(6).collect::<Vec<i64>>(), second.iter().take(6).collect::<Vec<i64>>());
+    /// ```
+    pub fn with_seed(roll: &str, seed: u64) -> Self {
+        let expr = parse_or_panic(roll);
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        Roller {
+            result: roll_or_panic(&expr, &mut rng),
+            expr,
+            rng: Some(rng),
+        }
+    }
+
+    /// Creates a new `Roller` for a roll with variables, such as `1d20 + @str_mod`, taking their values
+    /// for the first roll from `vars`. Later rolls take their values from `Roller::reroll_in`, so that
+    /// changes to the values are picked up by the next roll.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the syntax is incorrect or the first roll fails, as with `Roller::new`, including when
+    /// `vars` has no value for one of the roll's variables.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use rouler::Roller;
+    ///
+    /// let mut fighter = HashMap::new();
+    /// fighter.insert("str_mod", 3);
+    /// let mut attack = Roller::new_in("1d20 + @str_mod", &fighter);
+    /// assert!(4 <= attack.total() && attack.total() <= 23);
+    ///
+    /// fighter.insert("str_mod", 4);
+    /// assert!(attack.reroll_in(&fighter) >= 5);
+    /// ```
+    pub fn new_in<V: Variables + ?Sized>(roll: &str, vars: &V) -> Self {
+        let expr = parse_or_panic(roll);
+        Roller {
+            result: roll_or_panic(&bind_or_panic(&expr, vars), &mut thread_rng()),
+            expr,
+            rng: None,
         }
     }
 
     /// Rolls the `Roller`'s die roll, stores the value in total, and then returns it. In this way, you can use
     /// `Roller::reroll()` in place as a value, rather than needing to call the method seperately.
     ///
+    /// # Panics
+    ///
+    /// Panics if the roll fails to evaluate, such as when a roll like `1d6 / (1d2 - 1)` divides by zero, or
+    /// when it has variables, which are given their values by `reroll_in`.
+    ///
     /// # Examples
     /// ```
     /// use rouler::Roller;
@@ -226,14 +671,83 @@ impl<'a> Roller<'a> {
     /// println!("{}", stat);                           // prints [3d6: 12]
     /// ```
     pub fn reroll(&mut self) -> i64 {
-        self.total = roll_dice(self.roll);
+        self.result = match self.rng {
+            Some(ref mut rng) => roll_or_panic(&self.expr, rng),
+            None => roll_or_panic(&self.expr, &mut thread_rng()),
+        };
+
+        self.total()
+    }
+
+    /// As `reroll`, but rolls the dice using the given random number generator rather than the
+    /// `Roller`'s own.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the roll fails to evaluate, as with `reroll`.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate rand;
+    /// extern crate rouler;
+    ///
+    /// use rand::{rngs::StdRng, SeedableRng};
+    /// use rouler::Roller;
+    ///
+    /// # fn main() {
+    /// let mut stat = Roller::new("3d6");
+    /// let first = stat.reroll_with(&mut StdRng::seed_from_u64(7));
+    ///
+    /// assert_eq!(stat.reroll_with(&mut StdRng::seed_from_u64(7)), first);
+    /// # }
+    /// ```
+    pub fn reroll_with<R: Rng + ?Sized>(&mut self, rng: &mut R) -> i64 {
+        self.result = roll_or_panic(&self.expr, rng);
 
-        self.total
+        self.total()
+    }
+
+    /// As `reroll`, but takes the value of each of the roll's variables from `vars`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the roll fails to evaluate, or if `vars` has no value for one of its variables.
+    pub fn reroll_in<V: Variables + ?Sized>(&mut self, vars: &V) -> i64 {
+        let expr = bind_or_panic(&self.expr, vars);
+        self.result = match self.rng {
+            Some(ref mut rng) => roll_or_panic(&expr, rng),
+            None => roll_or_panic(&expr, &mut thread_rng()),
+        };
+
+        self.total()
     }
 
     /// Returns the result of the last roll made by the `Roller`.
     pub fn total(&self) -> i64 {
-        self.total
+        self.result.total()
+    }
+
+    /// Returns a breakdown of the last roll made by the `Roller`, including the value of every die.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rouler::{Roller, RollResult};
+    ///
+    /// let roller = Roller::new("3d6");
+    ///
+    /// if let RollResult::Dice(ref dice) = *roller.result() {
+    ///     let faces = dice.dice.iter().map(|die| die.value.to_string()).collect::<Vec<_>>();
+    ///     println!("Rolled {}", faces.join(", ")); // => Rolled 4, 2, 6
+    /// }
+    /// ```
+    pub fn result(&self) -> &RollResult {
+        &self.result
+    }
+
+    /// Returns the parsed form of the `Roller`'s die roll.
+    pub fn expr(&self) -> &Expr {
+        &self.expr
     }
 
     /// Returns a reference to self for use as an `Iterator`. This allows for iterating infinitely and lazily over
@@ -267,7 +781,7 @@ impl<'a> Roller<'a> {
     }
 }
 
-impl<'a> Iterator for Roller<'a> {
+impl Iterator for Roller {
     type Item = i64;
 
     fn next(&mut self) -> Option<i64> {
@@ -275,28 +789,42 @@ impl<'a> Iterator for Roller<'a> {
     }
 }
 
-impl<'a> PartialEq for Roller<'a> {
+impl PartialEq for Roller {
     fn eq(&self, other: &Roller) -> bool {
-        self.total == other.total
+        self.total() == other.total()
     }
 }
 
-impl<'a> Eq for Roller<'a> {}
+impl Eq for Roller {}
 
-impl<'a> Ord for Roller<'a> {
+impl Ord for Roller {
     fn cmp(&self, other: &Roller) -> Ordering {
-        self.total.cmp(&other.total)
+        self.total().cmp(&other.total())
     }
 }
 
-impl<'a> PartialOrd for Roller<'a> {
+impl PartialOrd for Roller {
     fn partial_cmp(&self, other: &Roller) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl<'a> fmt::Display for Roller<'a> {
+/// Displays the `Roller` as its syntax and total, eg. `[4d8 + 5: 24]`. The alternate form, `{:#}`, shows a
+/// breakdown of every die instead of only the total, eg. `[4d8 + 5: (7+2+6+4)+5 = 24]`.
+impl fmt::Display for Roller {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "[{}: {}]", self.roll, self.total)
+        if f.alternate() {
+            write!(f, "[{}: {}]", self.expr.as_str(), self.result)
+        } else {
+            write!(f, "[{}: {}]", self.expr.as_str(), self.total())
+        }
+    }
+}
+
+impl FromStr for Roller {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        roller_or_fail(s)
     }
 }
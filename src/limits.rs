@@ -0,0 +1,142 @@
+// rouler - A container-based system for generating die rolls
+// Copyright (C) 2016 by John Berry
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::fmt;
+
+use error::{Error, Limit};
+
+/// What happens when a roll goes over one of its `Limits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// The roll is cut down to fit within the limit, and a `Warning` is noted on the `Expr`. Rerolls and
+    /// explosions stop once their budget runs out, with a `Warning` noted on the roll's `RollResult`.
+    Clamp,
+    /// The roll fails with `Error::LimitExceeded`.
+    Error,
+}
+
+/// The limits placed on reading and rolling an expression, given to
+/// [`parse_with_limits`](fn.parse_with_limits.html).
+///
+/// The default limits allow 1000 dice per die code and up to 2<sup>32</sup> - 1 sides, clamping
+/// rolls which go over them. A public bot might tighten them and refuse any roll that goes over, while
+/// an offline simulator might raise them.
+///
+/// # Examples
+///
+/// ```
+/// use rouler::{parse_with_limits, Error, Limit, Limits, Policy};
+///
+/// let strict = Limits { dice: 100, policy: Policy::Error, ..Limits::default() };
+/// assert_eq!(
+///     parse_with_limits("5000d6", &strict).err(),
+///     Some(Error::LimitExceeded { limit: Limit::Dice, max: 100 })
+/// );
+///
+/// let lenient = Limits { dice: 100, ..Limits::default() };
+/// let expr = parse_with_limits("5000d6", &lenient).unwrap();
+/// assert_eq!(expr.max(), Ok(600));
+/// assert_eq!(expr.warnings()[0].to_string(), "too many dice, so only 100 were used");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The number of dice a single die code may roll, including any rerolls and explosions.
+    pub dice: u64,
+    /// The number of dice a whole expression may roll, including any rerolls and explosions.
+    pub total_dice: u64,
+    /// The number of sides on a single numbered die, or on a percentile or Fate die, which are cut down to
+    /// their first faces if they have too many.
+    pub sides: u64,
+    /// The number of faces listed on a single custom die.
+    pub custom_sides: u64,
+    /// How deeply parentheses, function arguments and unary minus signs may be nested. An expression that
+    /// nests them too deeply is always an error.
+    pub depth: u64,
+    /// The number of times the dice of a single die code may explode.
+    pub explosions: u64,
+    /// What happens when a roll goes over one of the limits.
+    pub policy: Policy,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            dice: 1000,
+            total_dice: u64::MAX,
+            sides: u32::MAX as u64,
+            custom_sides: 1000,
+            depth: 64,
+            explosions: u64::MAX,
+            policy: Policy::Clamp,
+        }
+    }
+}
+
+impl Limits {
+    /// The largest value `limit` allows.
+    pub(crate) fn max(&self, limit: Limit) -> u64 {
+        match limit {
+            Limit::Dice => self.dice,
+            Limit::TotalDice => self.total_dice,
+            Limit::Sides => self.sides,
+            Limit::CustomSides => self.custom_sides,
+            Limit::Depth => self.depth,
+            Limit::Explosions => self.explosions,
+            // the number of outcomes in a distribution and the work of finding them aren't configurable
+            Limit::Outcomes | Limit::Work => u64::MAX,
+        }
+    }
+
+    /// Checks `value` against `limit`, returning it cut down to the limit if the policy allows, and noting
+    /// a warning if it was.
+    pub(crate) fn check(&self, limit: Limit, value: u64, warnings: &mut Vec<Warning>) -> Result<u64, Error> {
+        let max = self.max(limit);
+        if value <= max {
+            return Ok(value);
+        }
+        self.exceeded(limit)?;
+        warnings.push(Warning::Clamped { limit, max });
+        Ok(max)
+    }
+
+    /// Fails with `LimitExceeded` if the policy doesn't allow going over limits.
+    pub(crate) fn exceeded(&self, limit: Limit) -> Result<(), Error> {
+        match self.policy {
+            Policy::Clamp => Ok(()),
+            Policy::Error => Err(Error::LimitExceeded { limit, max: self.max(limit) }),
+        }
+    }
+}
+
+/// Something in an expression that didn't stop it being read or rolled, but may not do what was meant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// A limit the expression went over, which was clamped rather than failing the roll.
+    Clamped {
+        /// Which limit was exceeded.
+        limit: Limit,
+        /// The largest value the limit allows, which was used in its place.
+        max: u64,
+    },
+    /// A die code with a success target standing where a comparison could have been meant. `2d20>=15`
+    /// counts the dice that roll 15 or more, while `2d20 >= 15`, with spaces, compares their total.
+    SuccessCount {
+        /// The die code and its target, as written.
+        notation: String,
+    },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Warning::Clamped { limit, max } => write!(f, "too many {}, so only {} were used", limit, max),
+            Warning::SuccessCount { ref notation } => {
+                write!(f, "`{}` counts the dice meeting its target, rather than comparing their total", notation)
+            },
+        }
+    }
+}
@@ -5,21 +5,36 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+// `PrecClimber` is deprecated in newer pest releases, but remains available throughout 2.x.
+#![allow(deprecated)]
+
+use std::str::FromStr;
+
 use pest::{
     prec_climber::*,
     iterators::*,
+    Parser,
 };
-use roll::{DieType, Roll, TargetRoll};
+use error::{syntax_error, Error, Limit};
+use expr::{Expr, Function, Node, Op};
+use limits::{Limits, Warning};
+use roll::{Comparison, DieType, Explode, Reroll, Roll, TargetRoll};
 
 lazy_static! {
     static ref PREC_CLIMBER: PrecClimber<Rule> = {
         use self::Assoc::*;
         use self::Rule::*;
 
-        // Order of precedence: "+-" is less than "*/"
+        // Order of precedence: "or" is less than "and", which is less than comparisons, which are less
+        // than "+-", which is less than "*/%", which is less than "^"
         PrecClimber::new(vec![
+            Operator::new(or, Left),
+            Operator::new(and, Left),
+            Operator::new(gt, Left) | Operator::new(gte, Left) | Operator::new(lt, Left)
+                | Operator::new(lte, Left) | Operator::new(eq, Left),
             Operator::new(plus, Left) | Operator::new(minus, Left),
-            Operator::new(times, Left) | Operator::new(slash, Left),
+            Operator::new(times, Left) | Operator::new(slash, Left) | Operator::new(modulo, Left),
+            Operator::new(power, Right),
         ])
     };
 }
@@ -28,69 +43,259 @@ lazy_static! {
 #[grammar = "rouler.pest"]
 pub struct RollParser;
 
-pub fn compute(expr: Pairs<Rule>) -> i64 {
-    let primary = |pair: Pair<Rule>| match pair.as_rule() {
-        Rule::uint => pair.as_str().parse::<u64>().unwrap() as i64,
-        Rule::int => pair.as_str().parse::<i64>().unwrap().into(),
-        Rule::expr => compute(pair.into_inner()),
-        Rule::roll => {
-            let mut inner = pair.into_inner();
-
-            let mut roll = Roll::new();
-
-            // Loop through the nested die rules
-            let mut inner_die = inner.next().unwrap().clone().into_inner();
-            while let Some(pair) = inner_die.next() {
-                match pair.as_rule() {
-                    Rule::count => {
-                        roll.count(pair.as_str().parse::<u64>().expect("Could not parse number of rolls"));
-                    },
-                    Rule::normal_die => {
-                        roll.sides(pair.as_str().parse::<u64>().expect("Could not parse number of sides"));
-                        roll.die_type(DieType::Normal);
-                    },
-                    Rule::custom_die => {
-                        let mut inner = pair.clone().into_inner();
-                        let mut sides = vec![];
-                        while let Some(side) = inner.next() {
-                            sides.push(side.as_str().parse::<i64>().expect("Could not parse custom side"));
-                        }
-                        roll.add_custom_sides(&sides);
-                        roll.die_type(DieType::Custom);
-                    },
-                    _ => unreachable!(),
-                }
-            }
+/// Parses a number matched by the grammar. The grammar only matches digits and an optional sign,
+/// so the only way this can fail is if the number is out of range for `T`.
+fn number<T: FromStr>(pair: &Pair<Rule>) -> Result<T, Error> {
+    pair.as_str().parse::<T>().map_err(|_| Error::Overflow)
+}
+
+fn target_roll(pair: Pair<Rule>) -> Result<TargetRoll, Error> {
+    let mut inner = pair.into_inner();
+    let compare = inner.next().unwrap();
+    let target = match inner.next() {
+        Some(target) => target,
+        // A bare number is an implicit equality check, eg. `1d6!3`
+        None => return Ok(TargetRoll::EQ(number(&compare)?)),
+    };
+    Ok(TargetRoll::new(comparison(&compare), number(&target)?))
+}
+
+fn comparison(pair: &Pair<Rule>) -> Comparison {
+    match pair.as_rule() {
+        Rule::gt => Comparison::GT,
+        Rule::gte => Comparison::GTE,
+        Rule::lt => Comparison::LT,
+        Rule::lte => Comparison::LTE,
+        Rule::eq => Comparison::EQ,
+        _ => unreachable!(),
+    }
+}
+
+fn explode(pair: Pair<Rule>) -> Result<Explode, Error> {
+    let mut inner = pair.into_inner();
+    let kind = inner.next().unwrap();
+    let compare_point = match inner.next() {
+        Some(compare_point) => Some(target_roll(compare_point)?),
+        None => None,
+    };
+    Ok(match kind.as_rule() {
+        Rule::exploding => Explode::Exploding(compare_point),
+        Rule::compounding => Explode::Compounding(compare_point),
+        Rule::penetrating => Explode::Penetrating(compare_point),
+        _ => unreachable!(),
+    })
+}
+
+fn reroll(pair: Pair<Rule>) -> Result<Reroll, Error> {
+    let mut inner = pair.into_inner();
+    let kind = inner.next().unwrap();
+    let compare_point = target_roll(inner.next().unwrap())?;
+    Ok(match kind.as_rule() {
+        Rule::reroll_always => Reroll::Always(compare_point),
+        Rule::reroll_once => Reroll::Once(compare_point),
+        _ => unreachable!(),
+    })
+}
+
+/// Checks each part of an expression against its limits as it is built.
+struct Builder<'a> {
+    limits: &'a Limits,
+    warnings: Vec<Warning>,
+    /// The dice rolled by the die codes built so far, before any rerolls or explosions.
+    dice: u64,
+}
+
+impl<'a> Builder<'a> {
+    fn check(&mut self, limit: Limit, value: u64) -> Result<u64, Error> {
+        self.limits.check(limit, value, &mut self.warnings)
+    }
 
-            // Invariant: This while loop should execute twice at most
-            // Once if there's a keep/drop and once if there's a target roll
-            while let Some(pair) = inner.next() {
-                let uint = inner.next().unwrap().as_str().parse::<u64>().expect("Could not parse uint");
-                match pair.as_rule() {
+    /// Checks the number of dice rolled by a die code against both its own limit and the expression's.
+    fn count(&mut self, count: u64) -> Result<u64, Error> {
+        let count = self.check(Limit::Dice, count)?;
+        let remaining = self.limits.total_dice.saturating_sub(self.dice);
+        let count = if count > remaining {
+            self.limits.exceeded(Limit::TotalDice)?;
+            self.warnings.push(Warning::Clamped { limit: Limit::TotalDice, max: self.limits.total_dice });
+            remaining
+        } else {
+            count
+        };
+        self.dice += count;
+        Ok(count)
+    }
+}
+
+fn roll(pair: Pair<Rule>, builder: &mut Builder) -> Result<Roll, Error> {
+    let mut roll = Roll::new();
+    roll.limits(*builder.limits);
+    roll.notation(pair.as_str());
+
+    let mut inner = pair.into_inner();
+
+    // Loop through the nested die rules
+    let inner_die = inner.next().unwrap().into_inner();
+    let mut count = 1;
+    for pair in inner_die {
+        match pair.as_rule() {
+            Rule::count => {
+                count = number(&pair)?;
+            },
+            // Fate and percentile dice are held to the limit on sides like any other, and clamping them
+            // leaves a die with only their first faces
+            Rule::fate_die => match builder.check(Limit::Sides, 3)? {
+                3 => {
+                    roll.die_type(DieType::Fate);
+                },
+                sides => {
+                    roll.add_custom_sides(&[-1, 0, 1][..sides as usize]);
+                    roll.die_type(DieType::Custom);
+                },
+            },
+            Rule::percentile_die => match builder.check(Limit::Sides, 100)? {
+                100 => {
+                    roll.die_type(DieType::Percentile);
+                },
+                sides => {
+                    roll.sides(sides);
+                    roll.die_type(DieType::Normal);
+                },
+            },
+            Rule::normal_die => {
+                roll.sides(builder.check(Limit::Sides, number(&pair)?)?);
+                roll.die_type(DieType::Normal);
+            },
+            Rule::custom_die => {
+                let mut sides = pair.into_inner()
+                    .map(|side| number(&side))
+                    .collect::<Result<Vec<i64>, Error>>()?;
+                let max = builder.check(Limit::CustomSides, sides.len() as u64)?;
+                sides.truncate(max as usize);
+                roll.add_custom_sides(&sides);
+                roll.die_type(DieType::Custom);
+            },
+            _ => unreachable!(),
+        }
+    }
+    roll.count(builder.count(count)?);
+
+    // Explosions and rerolls may be given in any order, followed by at most
+    // one keep/drop and one target roll
+    for pair in inner {
+        match pair.as_rule() {
+            Rule::explode => {
+                roll.explode(explode(pair)?);
+            },
+            Rule::reroll => {
+                roll.reroll(reroll(pair)?);
+            },
+            Rule::take => {
+                let mut inner = pair.into_inner();
+                let take = inner.next().unwrap();
+                let uint = number(&inner.next().unwrap())?;
+                match take.as_rule() {
                     Rule::keep => roll.keep_highest(uint),
+                    Rule::keep_lowest => roll.keep_lowest(uint),
                     Rule::drop => roll.drop_lowest(uint),
-                    Rule::gt => roll.target_roll(TargetRoll::GT(uint)),
-                    Rule::gte => roll.target_roll(TargetRoll::GTE(uint)),
-                    Rule::lt => roll.target_roll(TargetRoll::LT(uint)),
-                    Rule::lte => roll.target_roll(TargetRoll::LTE(uint)),
-                    Rule::eq => roll.target_roll(TargetRoll::EQ(uint)),
+                    Rule::drop_highest => roll.drop_highest(uint),
                     _ => unreachable!(),
                 };
-            }
+            },
+            Rule::target_roll => {
+                roll.target_roll(target_roll(pair)?);
+            },
+            _ => unreachable!(),
+        };
+    }
 
-            roll.roll_dice()
+    Ok(roll)
+}
+
+pub fn parse_expr(input: &str, limits: &Limits) -> Result<Expr, Error> {
+    let mut calc = RollParser::parse(Rule::calc, input).map_err(syntax_error)?;
+    let mut builder = Builder { limits, warnings: Vec::new(), dice: 0 };
+    let root = build(calc.next().unwrap().into_inner(), &mut builder, 0)?;
+    warn_success_counts(&root, true, &mut builder.warnings);
+    let extra_dice = limits.total_dice.saturating_sub(builder.dice);
+    Ok(Expr::new(root, input, extra_dice, builder.warnings))
+}
+
+/// Notes a warning for each die code counting successes where a comparison of its total could have been
+/// meant: as the whole expression, or as an operand of a comparison, `and` or `or`.
+fn warn_success_counts(node: &Node, compared: bool, warnings: &mut Vec<Warning>) {
+    match *node {
+        Node::Roll(ref roll) if compared => {
+            if let Some(notation) = roll.success_count() {
+                warnings.push(Warning::SuccessCount { notation: notation.to_string() });
+            }
         },
+        Node::Negation(ref operand) => warn_success_counts(operand, false, warnings),
+        Node::Operation(op, ref lhs, ref rhs) => {
+            let compared = matches!(op, Op::Compare(_) | Op::And | Op::Or);
+            warn_success_counts(lhs, compared, warnings);
+            warn_success_counts(rhs, compared, warnings);
+        },
+        Node::Call(_, ref args) => {
+            for arg in args {
+                warn_success_counts(arg, false, warnings);
+            }
+        },
+        _ => {},
+    }
+}
+
+/// Builds an expression nested within another, such as one in parentheses, a function's argument or the
+/// operand of a unary minus.
+fn nested(expr: Pair<Rule>, builder: &mut Builder, depth: u64) -> Result<Node, Error> {
+    // nesting too deeply is always an error, as there's no way to clamp it
+    if depth >= builder.limits.depth {
+        return Err(Error::LimitExceeded { limit: Limit::Depth, max: builder.limits.depth });
+    }
+    build(expr.into_inner(), builder, depth + 1)
+}
+
+fn call(pair: Pair<Rule>, builder: &mut Builder, depth: u64) -> Result<Node, Error> {
+    let mut pairs = pair.into_inner();
+    let function = match pairs.next().unwrap().as_rule() {
+        Rule::floor => Function::Floor,
+        Rule::ceil => Function::Ceil,
+        Rule::round => Function::Round,
+        Rule::abs => Function::Abs,
+        Rule::min => Function::Min,
+        Rule::max => Function::Max,
         _ => unreachable!(),
     };
+    let args = pairs.map(|arg| nested(arg, builder, depth)).collect::<Result<Vec<Node>, Error>>()?;
+    Ok(Node::Call(function, args))
+}
 
-    let infix = |lhs: i64, op: Pair<Rule>, rhs: i64| match op.as_rule() {
-        Rule::plus => lhs + rhs,
-        Rule::minus => lhs - rhs,
-        Rule::times => lhs * rhs,
-        Rule::slash => lhs / rhs,
+fn build(expr: Pairs<Rule>, builder: &mut Builder, depth: u64) -> Result<Node, Error> {
+    let primary = |pair: Pair<Rule>| match pair.as_rule() {
+        Rule::int => Ok(Node::Number(number(&pair)?)),
+        Rule::expr => nested(pair, builder, depth),
+        Rule::roll => Ok(Node::Roll(roll(pair, builder)?)),
+        Rule::call => call(pair, builder, depth),
+        Rule::negation => Ok(Node::Negation(Box::new(nested(pair, builder, depth)?))),
+        Rule::variable => Ok(Node::Variable(pair.into_inner().next().unwrap().as_str().to_string())),
         _ => unreachable!(),
     };
 
+    let infix = |lhs: Result<Node, Error>, op: Pair<Rule>, rhs: Result<Node, Error>| {
+        let op = match op.as_rule() {
+            Rule::plus => Op::Add,
+            Rule::minus => Op::Subtract,
+            Rule::times => Op::Multiply,
+            Rule::slash => Op::Divide,
+            Rule::modulo => Op::Modulo,
+            Rule::power => Op::Power,
+            Rule::gt | Rule::gte | Rule::lt | Rule::lte | Rule::eq => Op::Compare(comparison(&op)),
+            Rule::and => Op::And,
+            Rule::or => Op::Or,
+            _ => unreachable!(),
+        };
+        Ok(Node::Operation(op, Box::new(lhs?), Box::new(rhs?)))
+    };
+
     PREC_CLIMBER.climb(
         expr,
         primary,
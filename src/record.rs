@@ -0,0 +1,137 @@
+// rouler - A container-based system for generating die rolls
+// Copyright (C) 2016 by John Berry
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use error::Error;
+use roll::{DieSource, Faces};
+
+/// A single die drawn while rolling an expression.
+///
+/// Every die drawn is recorded, including dice that were later rerolled, dropped or added by explosions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Draw {
+    /// The die code the die was drawn for, as it was written, eg. `4d6kh3`.
+    pub term: String,
+    /// The position of the die code among the die codes of the expression, counting from zero.
+    pub term_index: usize,
+    /// The position of the die among the dice drawn for its die code, counting from zero.
+    pub index: usize,
+    /// The face the die landed on.
+    pub face: i64,
+}
+
+/// A record of every die drawn while rolling an expression, made by `Expr::roll_recorded`.
+///
+/// Replaying the log with `Expr::replay` repeats the roll exactly, giving an identical breakdown.
+///
+/// # Examples
+///
+/// ```
+/// use rouler::parse;
+///
+/// let attack = parse("1d20 + 5").unwrap();
+/// let (result, log) = attack.roll_recorded(&mut rand::thread_rng()).unwrap();
+///
+/// assert_eq!(log.expression, "1d20 + 5");
+/// assert_eq!(log.draws[0].term, "1d20");
+/// assert_eq!(attack.replay(&log), Ok(result));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RollLog {
+    /// The expression that was rolled.
+    pub expression: String,
+    /// Every die drawn, in the order it was drawn.
+    pub draws: Vec<Draw>,
+}
+
+/// Passes dice drawn from another source through, recording each of them.
+pub(crate) struct Recorder<'a> {
+    inner: &'a mut dyn DieSource,
+    draws: Vec<Draw>,
+    term: String,
+    terms: usize,
+    index: usize,
+}
+
+impl<'a> Recorder<'a> {
+    pub(crate) fn new(inner: &'a mut dyn DieSource) -> Self {
+        Recorder { inner, draws: Vec::new(), term: String::new(), terms: 0, index: 0 }
+    }
+
+    pub(crate) fn finish(self, expression: &str) -> RollLog {
+        RollLog { expression: expression.to_string(), draws: self.draws }
+    }
+}
+
+impl<'a> DieSource for Recorder<'a> {
+    fn begin(&mut self, notation: &str) {
+        self.term = notation.to_string();
+        self.terms += 1;
+        self.index = 0;
+        self.inner.begin(notation);
+    }
+
+    fn draw(&mut self, faces: &Faces) -> i64 {
+        let face = self.inner.draw(faces);
+        self.draws.push(Draw { term: self.term.clone(), term_index: self.terms - 1, index: self.index, face });
+        self.index += 1;
+        face
+    }
+}
+
+/// Draws dice from a recorded log, noting the first draw which doesn't match the roll being replayed.
+pub(crate) struct Replayer<'a> {
+    draws: &'a [Draw],
+    next: usize,
+    term: String,
+    terms: usize,
+    index: usize,
+    mismatch: Option<usize>,
+}
+
+impl<'a> Replayer<'a> {
+    pub(crate) fn new(log: &'a RollLog) -> Self {
+        Replayer { draws: &log.draws, next: 0, term: String::new(), terms: 0, index: 0, mismatch: None }
+    }
+
+    /// Checks that the roll used every draw in the log, and that each of them matched.
+    pub(crate) fn finish(self) -> Result<(), Error> {
+        match self.mismatch {
+            Some(draw) => Err(Error::ReplayMismatch { draw }),
+            None if self.next < self.draws.len() => Err(Error::ReplayMismatch { draw: self.next }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> DieSource for Replayer<'a> {
+    fn begin(&mut self, notation: &str) {
+        self.term = notation.to_string();
+        self.terms += 1;
+        self.index = 0;
+    }
+
+    fn draw(&mut self, faces: &Faces) -> i64 {
+        let position = self.next;
+        self.next += 1;
+        self.index += 1;
+
+        match self.draws.get(position) {
+            Some(draw) if draw.term == self.term
+                && draw.term_index + 1 == self.terms
+                && draw.index + 1 == self.index
+                && faces.contains(draw.face) => draw.face,
+            _ => {
+                self.mismatch = self.mismatch.or(Some(position));
+                // the roll can't be replayed, but carries on with any valid face until it finishes
+                match *faces {
+                    Faces::Between(low, _) => low,
+                    Faces::Listed(faces) => faces[0],
+                }
+            },
+        }
+    }
+}
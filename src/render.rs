@@ -0,0 +1,186 @@
+// rouler - A container-based system for generating die rolls
+// Copyright (C) 2016 by John Berry
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::fmt;
+
+use expr::{Function, Op};
+use result::{DiceResult, Die, RollResult};
+use roll::{Comparison, DieType};
+
+/// The formats in which a `RollResult` can be rendered as a breakdown.
+///
+/// Each die code is shown as its individual dice in parentheses, with dropped dice struck
+/// through and successes highlighted, eg. `(3+5+~~1~~)+4 = 12` in Markdown. Fate dice are
+/// shown as `[+]`, `[-]` and `[ ]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// Plain text, with dropped dice as `~1~` and successes as `*6*`.
+    Plain,
+    /// Markdown, with dropped dice as `~~1~~` and successes as `**6**`.
+    Markdown,
+    /// Text coloured with ANSI escape codes, with dropped dice dimmed and struck through, and
+    /// successes in bold green.
+    Ansi,
+}
+
+impl Style {
+    fn dropped(self, die: &str) -> String {
+        match self {
+            Style::Plain => format!("~{}~", die),
+            Style::Markdown => format!("~~{}~~", die),
+            Style::Ansi => format!("\x1b[2;9m{}\x1b[0m", die),
+        }
+    }
+
+    fn success(self, die: &str) -> String {
+        match self {
+            Style::Plain => format!("*{}*", die),
+            Style::Markdown => format!("**{}**", die),
+            Style::Ansi => format!("\x1b[1;32m{}\x1b[0m", die),
+        }
+    }
+
+    fn op(self, op: Op) -> &'static str {
+        match op {
+            Op::Add => "+",
+            Op::Subtract => "-",
+            // An unescaped `*` would start emphasis in Markdown
+            Op::Multiply if self == Style::Markdown => "\\*",
+            Op::Multiply => "*",
+            Op::Divide => "/",
+            Op::Modulo => "%",
+            // `^` marks superscript in some flavours of Markdown
+            Op::Power if self == Style::Markdown => "\\^",
+            Op::Power => "^",
+            Op::Compare(Comparison::GT) => ">",
+            Op::Compare(Comparison::GTE) => ">=",
+            Op::Compare(Comparison::LT) => "<",
+            Op::Compare(Comparison::LTE) => "<=",
+            Op::Compare(Comparison::EQ) => "==",
+            Op::And => " and ",
+            Op::Or => " or ",
+        }
+    }
+}
+
+impl RollResult {
+    /// Renders the roll as a breakdown of every die, followed by the total.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rouler::{Roller, Style};
+    ///
+    /// let roller = Roller::new("3d1kh2 + 4");
+    ///
+    /// assert_eq!(roller.result().render(Style::Plain), "(~1~+1+1)+4 = 6");
+    /// assert_eq!(roller.result().render(Style::Markdown), "(~~1~~+1+1)+4 = 6");
+    /// ```
+    pub fn render(&self, style: Style) -> String {
+        format!("{} = {}", self.breakdown(style), self.total())
+    }
+
+    fn breakdown(&self, style: Style) -> String {
+        match *self {
+            RollResult::Number(number) => number.to_string(),
+            RollResult::Dice(ref dice) => dice.breakdown(style),
+            RollResult::Negation { ref operand, .. } => match **operand {
+                RollResult::Operation { .. } => format!("-({})", operand.breakdown(style)),
+                _ => format!("-{}", operand.breakdown(style)),
+            },
+            RollResult::Operation { op, ref lhs, ref rhs, .. } => {
+                let lhs_breakdown = lhs.breakdown(style);
+                let rhs_breakdown = rhs.breakdown(style);
+                let lhs_breakdown = if lhs.binds_looser(op, false) { format!("({})", lhs_breakdown) } else { lhs_breakdown };
+                let rhs_breakdown = if rhs.binds_looser(op, true) { format!("({})", rhs_breakdown) } else { rhs_breakdown };
+                format!("{}{}{}", lhs_breakdown, style.op(op), rhs_breakdown)
+            },
+            RollResult::Call { function, ref args, .. } => {
+                let args = args.iter().map(|arg| arg.breakdown(style)).collect::<Vec<String>>();
+                format!("{}({})", name(function), args.join(", "))
+            },
+        }
+    }
+
+    /// Whether this result needs parentheses to appear as an operand of `parent`.
+    fn binds_looser(&self, parent: Op, right: bool) -> bool {
+        match *self {
+            RollResult::Operation { op, .. } => {
+                let (child, parent_precedence) = (precedence(op), precedence(parent));
+                child < parent_precedence || (child == parent_precedence && if parent == Op::Power {
+                    // `^` groups to the right, so it is its left operand which needs parentheses to group first
+                    !right
+                } else {
+                    // Only sums, products, `and` and `or` are unchanged by grouping to the right, as `/` and `%`
+                    // round
+                    right && !([Op::Add, Op::And, Op::Or].contains(&parent) || (parent == Op::Multiply && op == Op::Multiply))
+                })
+            },
+            // Powers are worked out before negation, so a negative base needs parentheses
+            RollResult::Negation { .. } => parent == Op::Power && !right,
+            RollResult::Number(number) => number < 0 && parent == Op::Power && !right,
+            _ => false,
+        }
+    }
+}
+
+fn precedence(op: Op) -> u8 {
+    match op {
+        Op::Or => 1,
+        Op::And => 2,
+        Op::Compare(_) => 3,
+        Op::Add | Op::Subtract => 4,
+        Op::Multiply | Op::Divide | Op::Modulo => 5,
+        Op::Power => 6,
+    }
+}
+
+fn name(function: Function) -> &'static str {
+    match function {
+        Function::Floor => "floor",
+        Function::Ceil => "ceil",
+        Function::Round => "round",
+        Function::Abs => "abs",
+        Function::Min => "min",
+        Function::Max => "max",
+    }
+}
+
+impl DiceResult {
+    fn breakdown(&self, style: Style) -> String {
+        let separator = if self.die_type == DieType::Fate { "" } else { "+" };
+        let dice = self.dice.iter().map(|die| self.render_die(die, style)).collect::<Vec<String>>();
+        format!("({})", dice.join(separator))
+    }
+
+    fn render_die(&self, die: &Die, style: Style) -> String {
+        let face = if self.die_type == DieType::Fate {
+            match die.value {
+                value if value > 0 => "[+]".to_string(),
+                value if value < 0 => "[-]".to_string(),
+                _ => "[ ]".to_string(),
+            }
+        } else {
+            die.value.to_string()
+        };
+
+        if die.dropped {
+            style.dropped(&face)
+        } else if die.success {
+            style.success(&face)
+        } else {
+            face
+        }
+    }
+}
+
+/// Displays the breakdown in the `Plain` style.
+impl fmt::Display for RollResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.render(Style::Plain))
+    }
+}
@@ -0,0 +1,188 @@
+// rouler - A container-based system for generating die rolls
+// Copyright (C) 2016 by John Berry
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use expr::{Function, Op};
+use limits::Warning;
+use roll::DieType;
+
+/// A breakdown of a single roll, showing how its total was reached.
+///
+/// The tree mirrors the expression that was rolled: every number, die code and arithmetic
+/// operation in the expression has a matching node, along with the value it contributed.
+///
+/// # Examples
+///
+/// ```
+/// use rouler::{parse, RollResult};
+///
+/// let result = parse("3d1 + 4").unwrap().roll().unwrap();
+/// assert_eq!(result.total(), 7);
+///
+/// if let RollResult::Operation { ref lhs, .. } = result {
+///     if let RollResult::Dice(ref dice) = **lhs {
+///         assert_eq!(dice.dice.len(), 3);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RollResult {
+    /// A plain number.
+    Number(i64),
+    /// The dice rolled for a single die code.
+    Dice(DiceResult),
+    /// A result negated by a unary minus, as in `-(1d4)`.
+    Negation {
+        /// The result that was negated.
+        operand: Box<RollResult>,
+        /// The negated value.
+        total: i64,
+    },
+    /// An arithmetic operation on two other results.
+    Operation {
+        /// The operator applied.
+        op: Op,
+        /// The result on the left hand side of the operator.
+        lhs: Box<RollResult>,
+        /// The result on the right hand side of the operator.
+        rhs: Box<RollResult>,
+        /// The value of the operation.
+        total: i64,
+    },
+    /// A function called on other results.
+    Call {
+        /// The function called.
+        function: Function,
+        /// The results of the function's arguments.
+        args: Vec<RollResult>,
+        /// The value the function returned.
+        total: i64,
+    },
+}
+
+/// The dice rolled for a single die code, such as `4d6kh3`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiceResult {
+    /// The type of dice rolled.
+    pub die_type: DieType,
+    /// Every die in the roll, in the order they were rolled. Dice added by explosions follow the die that
+    /// exploded, and a die that was rerolled shows only its final face.
+    pub dice: Vec<Die>,
+    /// Whether the roll counted successes against a target, rather than adding up its dice.
+    pub counts_successes: bool,
+    /// The sum of the dice that were kept, or the number of successes.
+    pub total: i64,
+    /// The limits on rerolls, explosions and dice that ran out while rolling, cutting the roll short
+    /// rather than failing it.
+    pub warnings: Vec<Warning>,
+}
+
+/// A single die within a `DiceResult`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Die {
+    /// The value of the die.
+    pub value: i64,
+    /// Whether the die was dropped by keeping or dropping dice, and so does not count toward the total.
+    pub dropped: bool,
+    /// Whether the die met the roll's success target. This is always false for rolls without a target.
+    pub success: bool,
+}
+
+impl RollResult {
+    /// Returns the value of this result.
+    pub fn total(&self) -> i64 {
+        match *self {
+            RollResult::Number(number) => number,
+            RollResult::Dice(ref dice) => dice.total,
+            RollResult::Negation { total, .. } => total,
+            RollResult::Operation { total, .. } => total,
+            RollResult::Call { total, .. } => total,
+        }
+    }
+
+    /// The limits that ran out while rolling, cutting rerolls or explosions short rather than failing the
+    /// roll. Limits the expression went over when it was parsed are given by `Expr::warnings`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rouler::{parse_with_limits, Limit, Limits, Warning};
+    ///
+    /// let limits = Limits { explosions: 2, ..Limits::default() };
+    /// let result = parse_with_limits("1d1!", &limits).unwrap().roll().unwrap();
+    ///
+    /// assert_eq!(result.total(), 3);
+    /// assert_eq!(result.warnings(), vec![Warning::Clamped { limit: Limit::Explosions, max: 2 }]);
+    /// ```
+    pub fn warnings(&self) -> Vec<Warning> {
+        match *self {
+            RollResult::Number(_) => Vec::new(),
+            RollResult::Dice(ref dice) => dice.warnings.clone(),
+            RollResult::Negation { ref operand, .. } => operand.warnings(),
+            RollResult::Operation { ref lhs, ref rhs, .. } => {
+                let mut warnings = lhs.warnings();
+                warnings.extend(rhs.warnings());
+                warnings
+            },
+            RollResult::Call { ref args, .. } => args.iter().flat_map(RollResult::warnings).collect(),
+        }
+    }
+
+    /// Whether a comparison, or comparisons joined by `and` or `or`, succeeded. Returns `None` for any other
+    /// result.
+    pub fn success(&self) -> Option<bool> {
+        match *self {
+            RollResult::Operation { op: Op::Compare(_), total, .. }
+            | RollResult::Operation { op: Op::And, total, .. }
+            | RollResult::Operation { op: Op::Or, total, .. } => Some(total != 0),
+            _ => None,
+        }
+    }
+
+    /// How far a comparison succeeded or failed by, such as 3 for a total of 18 against `>= 15`, or -2 for a
+    /// total of 13. The margin is measured from the nearest total that would succeed, which for `>` and `<`
+    /// is one past the target, so 15 against `> 15` fails by 1. It is negative exactly when the comparison
+    /// fails. Returns `None` for any result other than a comparison.
+    ///
+    /// Comparisons joined by `and` have the smaller of their margins, and those joined by `or` the larger.
+    /// An `and` or `or` with a plain total on either side, as in `0 and 1d20 > 4`, has no margin.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rouler::parse;
+    ///
+    /// let attack = parse("3d1 + 5 >= 10").unwrap().roll().unwrap();
+    ///
+    /// assert_eq!(attack.success(), Some(false));
+    /// assert_eq!(attack.margin(), Some(-2));
+    ///
+    /// let check = parse("1d1 + 14 > 15").unwrap().roll().unwrap();
+    ///
+    /// assert_eq!(check.margin(), Some(-1));
+    /// ```
+    pub fn margin(&self) -> Option<i64> {
+        match *self {
+            RollResult::Operation { op: Op::Compare(comparison), ref lhs, ref rhs, .. } => {
+                Some(comparison.margin(lhs.total(), rhs.total()))
+            },
+            RollResult::Operation { op: op @ Op::And, ref lhs, ref rhs, .. }
+            | RollResult::Operation { op: op @ Op::Or, ref lhs, ref rhs, .. } => match (lhs.margin(), rhs.margin()) {
+                (Some(lhs), Some(rhs)) if op == Op::And => Some(lhs.min(rhs)),
+                (Some(lhs), Some(rhs)) => Some(lhs.max(rhs)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl DiceResult {
+    /// Returns the dice that count toward the total, skipping any that were dropped.
+    pub fn kept(&self) -> impl Iterator<Item = &Die> {
+        self.dice.iter().filter(|die| !die.dropped)
+    }
+}
@@ -5,36 +5,187 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use rand::{distributions::{Distribution, Uniform}, thread_rng};
+use std::convert::TryFrom;
 
-const MAX_ROLLS: u64 = 1000;
-const MAX_SIDES: u64 = u32::max_value() as u64;
-const MAX_CUSTOM_SIDES: usize = 1000;
+use rand::{distributions::{Distribution as _, Uniform}, seq::SliceRandom, Rng};
 
+use distribution::Distribution;
+use error::{Error, Limit};
+use limits::{Limits, Warning};
+use result::{DiceResult, Die};
+
+/// Once the chance of a die exploding yet again falls below this, the distribution of a roll
+/// stops following its explosions.
+const NEGLIGIBLE: f64 = 1e-12;
+
+/// The kinds of die that can be rolled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DieType {
+    /// A die with a list of faces, such as `d[1, 3, 5]`.
     Custom,
+    /// A Fate die, `dF`, with faces of -1, 0 and +1.
+    Fate,
+    /// A die numbered from one to its number of sides, such as `d6`.
     Normal,
+    /// A percentile die, `d%`, numbered from 1 to 100.
+    Percentile,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub enum Take {
     KeepHighest(u64),
+    KeepLowest(u64),
+    DropHighest(u64),
     DropLowest(u64),
 }
 
-#[derive(Clone, Copy)]
+impl Take {
+    /// Marks which of `results` are kept, leaving the rolled order of the dice unchanged.
+    pub fn kept(&self, results: &[i64]) -> Vec<bool> {
+        // sort by ascending, with the earlier of two equal dice counting as lower
+        let mut order = (0..results.len()).collect::<Vec<usize>>();
+        order.sort_by_key(|&i| results[i]);
+
+        let len = results.len();
+        let limit_take = |take_num: u64| -> usize {
+            if take_num > len as u64 {
+                len
+            } else {
+                take_num as usize
+            }
+        };
+
+        let dropped = match *self {
+            Take::KeepHighest(kh) => &order[..len - limit_take(kh)],
+            Take::KeepLowest(kl) => &order[limit_take(kl)..],
+            Take::DropHighest(dh) => &order[len - limit_take(dh)..],
+            Take::DropLowest(dl) => &order[..limit_take(dl)],
+        };
+
+        let mut kept = vec![true; len];
+        for &i in dropped {
+            kept[i] = false;
+        }
+        kept
+    }
+
+    /// How many of `count` dice are kept, and whether they are the highest dice rather than the lowest.
+    fn keeps(&self, count: u64) -> (u64, bool) {
+        match *self {
+            Take::KeepHighest(kh) => (kh.min(count), true),
+            Take::KeepLowest(kl) => (kl.min(count), false),
+            Take::DropHighest(dh) => (count - dh.min(count), false),
+            Take::DropLowest(dl) => (count - dl.min(count), true),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Explode {
+    Exploding(Option<TargetRoll>),
+    Compounding(Option<TargetRoll>),
+    Penetrating(Option<TargetRoll>),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Reroll {
+    Always(TargetRoll),
+    Once(TargetRoll),
+}
+
+/// The ways in which a roll can be compared against a target number, as in success targets such as
+/// `5d10>=8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Comparison {
+    /// `>`
+    GT,
+    /// `>=`
+    GTE,
+    /// `<`
+    LT,
+    /// `<=`
+    LTE,
+    /// `==`
+    EQ,
+}
+
+impl Comparison {
+    /// Whether `value` compares to `target` in this way.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rouler::Comparison;
+    ///
+    /// assert!(Comparison::GTE.matches(16, 16));
+    /// assert!(!Comparison::GT.matches(16, 16));
+    /// ```
+    pub fn matches(self, value: i64, target: i64) -> bool {
+        match self {
+            Comparison::GT => value > target,
+            Comparison::GTE => value >= target,
+            Comparison::LT => value < target,
+            Comparison::LTE => value <= target,
+            Comparison::EQ => value == target,
+        }
+    }
+
+    /// How far `value` is past the nearest value meeting `target`, in the direction the comparison looks for,
+    /// which is negative when it falls short. An exact match can only fall short.
+    pub(crate) fn margin(self, value: i64, target: i64) -> i64 {
+        match self {
+            Comparison::GT => value.saturating_sub(target).saturating_sub(1),
+            Comparison::GTE => value.saturating_sub(target),
+            Comparison::LT => target.saturating_sub(value).saturating_sub(1),
+            Comparison::LTE => target.saturating_sub(value),
+            Comparison::EQ => value.saturating_sub(target).saturating_abs().saturating_neg(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum TargetRoll {
-    GT(u64),
-    GTE(u64),
-    LT(u64),
-    LTE(u64),
-    EQ(u64),
+    GT(i64),
+    GTE(i64),
+    LT(i64),
+    LTE(i64),
+    EQ(i64),
+}
+
+impl TargetRoll {
+    pub fn new(comparison: Comparison, target_number: i64) -> Self {
+        match comparison {
+            Comparison::GT => TargetRoll::GT(target_number),
+            Comparison::GTE => TargetRoll::GTE(target_number),
+            Comparison::LT => TargetRoll::LT(target_number),
+            Comparison::LTE => TargetRoll::LTE(target_number),
+            Comparison::EQ => TargetRoll::EQ(target_number),
+        }
+    }
+
+    pub fn matches(&self, roll: i64) -> bool {
+        let (comparison, target_number) = match *self {
+            TargetRoll::GT(target_number) => (Comparison::GT, target_number),
+            TargetRoll::GTE(target_number) => (Comparison::GTE, target_number),
+            TargetRoll::LT(target_number) => (Comparison::LT, target_number),
+            TargetRoll::LTE(target_number) => (Comparison::LTE, target_number),
+            TargetRoll::EQ(target_number) => (Comparison::EQ, target_number),
+        };
+        comparison.matches(roll, target_number)
+    }
 }
 
+#[derive(Debug, Clone)]
 pub struct Roll {
     count: u64,
     custom_sides: Vec<i64>,
     die_type: DieType,
+    explode: Option<Explode>,
+    limits: Limits,
+    notation: String,
+    rerolls: Vec<Reroll>,
     sides: u64,
     take: Option<Take>,
     target_roll: Option<TargetRoll>,
@@ -46,297 +197,999 @@ impl Roll {
             count: 1,
             custom_sides: Vec::new(),
             die_type: DieType::Normal,
+            explode: None,
+            limits: Limits::default(),
+            notation: String::new(),
+            rerolls: Vec::new(),
             sides: 20,
             take: None,
             target_roll: None,
         }
     }
 
-    pub fn add_custom_sides<'a>(&'a mut self, custom_sides: &[i64]) -> &'a mut Self {
+    pub fn add_custom_sides(&mut self, custom_sides: &[i64]) -> &mut Self {
         self.custom_sides.extend(custom_sides);
         self
     }
 
-    pub fn count<'a>(&'a mut self, count: u64) -> &'a mut Self {
-        let count = if count > MAX_ROLLS {
-            MAX_ROLLS
-        } else {
-            count
-        };
-        self.count = count;
+    pub fn count(&mut self, count: u64) -> &mut Self {
+        self.count = count.min(self.limits.dice);
         self
     }
 
-    pub fn die_type<'a>(&'a mut self, die_type: DieType) -> &'a mut Self {
+    pub fn die_type(&mut self, die_type: DieType) -> &mut Self {
         self.die_type = die_type;
         self
     }
 
-    pub fn drop_lowest<'a>(&'a mut self, drop_lowest: u64) -> &'a mut Self {
+    pub fn drop_highest(&mut self, drop_highest: u64) -> &mut Self {
+        self.take = Some(Take::DropHighest(drop_highest));
+        self
+    }
+
+    pub fn drop_lowest(&mut self, drop_lowest: u64) -> &mut Self {
         self.take = Some(Take::DropLowest(drop_lowest));
         self
     }
 
-    pub fn keep_highest<'a>(&'a mut self, keep_highest: u64) -> &'a mut Self {
+    pub fn explode(&mut self, explode: Explode) -> &mut Self {
+        self.explode = Some(explode);
+        self
+    }
+
+    pub fn keep_highest(&mut self, keep_highest: u64) -> &mut Self {
         self.take = Some(Take::KeepHighest(keep_highest));
         self
     }
 
-    pub fn sides<'a>(&'a mut self, sides: u64) -> &'a mut Self {
-        let sides = if sides > MAX_SIDES {
-            MAX_SIDES
-        } else {
-            sides
-        };
-        self.sides = sides;
+    pub fn keep_lowest(&mut self, keep_lowest: u64) -> &mut Self {
+        self.take = Some(Take::KeepLowest(keep_lowest));
+        self
+    }
+
+    /// Sets the limits the roll is made within. Limits should be set before the count and sides, which
+    /// are clamped to them.
+    pub fn limits(&mut self, limits: Limits) -> &mut Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn notation(&mut self, notation: &str) -> &mut Self {
+        self.notation = notation.to_string();
         self
     }
 
-    pub fn target_roll<'a>(&'a mut self, target_roll: TargetRoll) -> &'a mut Self {
+    pub fn reroll(&mut self, reroll: Reroll) -> &mut Self {
+        self.rerolls.push(reroll);
+        self
+    }
+
+    pub fn sides(&mut self, sides: u64) -> &mut Self {
+        self.sides = sides.min(self.limits.sides);
+        self
+    }
+
+    pub fn target_roll(&mut self, target_roll: TargetRoll) -> &mut Self {
         self.target_roll = Some(target_roll);
         self
     }
 
-    pub fn roll_dice(&self) -> i64 {
-        match self.die_type {
-            DieType::Custom => self.roll_custom_dice(),
-            DieType::Normal => self.roll_normal_dice() as i64,
+    /// Works out the exact chance of each total the roll can produce.
+    ///
+    /// Explosions are followed until the chance of another is negligible or the roll's budget of dice
+    /// runs out, with each die given the whole budget. Exploding and penetrating dice which are also kept
+    /// or dropped can't be analysed.
+    pub fn distribution(&self) -> Result<Distribution, Error> {
+        match self.take {
+            Some(take) => {
+                let (keep, highest) = take.keeps(self.count);
+                self.die_distribution()?.keep(self.count, keep, highest, |value| self.score(value))
+            },
+            None => self.scored_die_distribution()?.repeat(self.count),
+        }
+    }
+
+    /// Works out the mean and variance of the roll's total from those of a single die, or `None` if the
+    /// roll keeps or drops dice, and so needs its whole distribution.
+    pub fn moments(&self) -> Result<Option<(f64, f64)>, Error> {
+        if self.take.is_some() {
+            return Ok(None);
         }
+        let die = self.scored_die_distribution()?;
+        let count = self.count as f64;
+        Ok(Some((count * die.mean(), count * die.variance())))
+    }
+
+    /// The lowest and highest totals the roll can produce.
+    pub fn bounds(&self) -> Result<(i64, i64), Error> {
+        if let (Some(explode), Some((faces, max_face))) = (self.explode, self.faces()?) {
+            let draw = self.reroll_distribution(&faces);
+            let count = self.take.map_or(self.count, |take| take.keeps(self.count).0);
+            match explode {
+                Explode::Exploding(compare_point) if self.take.is_none() => {
+                    let explodes_on = |face| explodes(compare_point, max_face, face);
+                    return explosion_bounds(&draw, explodes_on, |face, _| Ok(self.score(face)), count, self.explosion_budget());
+                },
+                Explode::Penetrating(compare_point) if self.take.is_none() => {
+                    let explodes_on = |face| explodes(compare_point, max_face, face);
+                    let penetrate = |face, extra| self.penetrate(face, extra);
+                    return explosion_bounds(&draw, explodes_on, penetrate, count, self.explosion_budget());
+                },
+                // A compounded die is scored once it has stopped, so with a success target it can only succeed
+                // or fail, as its distribution shows. Those kept from a roll never total more than the same
+                // number of dice given the whole budget.
+                Explode::Compounding(compare_point) if self.target_roll.is_none() => {
+                    let explodes_on = |face| explodes(compare_point, max_face, face);
+                    return explosion_bounds(&draw, explodes_on, |face, _| Ok(face), count, self.explosion_budget());
+                },
+                _ => {},
+            }
+        }
+
+        let (die, count) = match self.take {
+            Some(take) => (self.die_distribution()?.map(|value| Ok(self.score(value)))?, take.keeps(self.count).0),
+            None => (self.scored_die_distribution()?, self.count),
+        };
+        let count = count as i64;
+        Ok((
+            die.min().checked_mul(count).ok_or(Error::Overflow)?,
+            die.max().checked_mul(count).ok_or(Error::Overflow)?,
+        ))
+    }
+
+    /// The most dice a single die can explode into within the roll's limits.
+    fn explosion_budget(&self) -> u64 {
+        self.limits.dice.saturating_sub(self.count).min(self.limits.explosions)
     }
 
-    fn roll_normal_dice(&self) -> u64 {
-        let mut results: Vec<u64> = if self.sides > 0 {
-            // The `rand` docs recommend constructing `Uniform` distribution to make
-            // sampling of multiple values faster.
-            let between = Uniform::from(1..(self.sides + 1));
-            let mut rng = thread_rng();
-            (0..self.count).map(|_| between.sample(&mut rng)).collect()
+    /// What a die showing `face` adds to the total when penetrating, where `extra` is false only for the
+    /// first die.
+    fn penetrate(&self, face: i64, extra: bool) -> Result<i64, Error> {
+        if extra {
+            face.checked_sub(1).map(|face| self.score(face)).ok_or(Error::Overflow)
         } else {
-            // zero-sided dice will always roll zero
-            vec![0; self.count as usize]
+            Ok(self.score(face))
+        }
+    }
+
+    /// The roll as written, if it counts the dice meeting a success target rather than adding them up.
+    pub(crate) fn success_count(&self) -> Option<&str> {
+        self.target_roll.map(|_| self.notation.as_str())
+    }
+
+    /// What a die showing `value` adds to the total.
+    fn score(&self, value: i64) -> i64 {
+        match self.target_roll {
+            Some(target_roll) => target_roll.matches(value) as i64,
+            None => value,
+        }
+    }
+
+    /// The chance of each face of a single die, along with its highest face, or `None` if it has no faces.
+    fn faces(&self) -> Result<Option<(Distribution, i64)>, Error> {
+        Ok(match self.die_type {
+            DieType::Custom if self.custom_sides.is_empty() => None,
+            DieType::Custom => {
+                let faces = self.custom_faces();
+                Some((Distribution::uniform(faces)?, *faces.iter().max().unwrap()))
+            },
+            DieType::Fate => Some((Distribution::between(-1, 1)?, 1)),
+            DieType::Normal if self.sides == 0 => None,
+            DieType::Normal => Some((Distribution::between(1, self.sides as i64)?, self.sides as i64)),
+            DieType::Percentile => Some((Distribution::between(1, 100)?, 100)),
+        })
+    }
+
+    /// Works out the chance of each value of a single die once it has been rerolled and compounded.
+    ///
+    /// The dice added by exploding and penetrating dice can't be told apart from the rest, so an error is
+    /// returned for them.
+    fn die_distribution(&self) -> Result<Distribution, Error> {
+        let (faces, max_face) = match self.faces()? {
+            Some(faces) => faces,
+            // as when rolling, dice without any faces always roll zero and are never rerolled or exploded
+            None => return Ok(Distribution::constant(0)),
+        };
+        let draw = self.reroll_distribution(&faces);
+        match self.explode {
+            None => Ok(draw),
+            Some(Explode::Compounding(compare_point)) => {
+                let explodes_on = |face| explodes(compare_point, max_face, face);
+                explode_distribution(&draw, explodes_on, |face, _| Ok(face), self.explosion_budget())
+            },
+            Some(_) => Err(Error::Unsupported),
+        }
+    }
+
+    /// Works out the chance of each amount a single die adds to the total, along with any dice it
+    /// explodes into.
+    fn scored_die_distribution(&self) -> Result<Distribution, Error> {
+        let (faces, max_face) = match self.faces()? {
+            Some(faces) => faces,
+            None => return Distribution::constant(0).map(|value| Ok(self.score(value))),
+        };
+        let budget = self.explosion_budget();
+        match self.explode {
+            Some(Explode::Exploding(compare_point)) => {
+                let explodes_on = |face| explodes(compare_point, max_face, face);
+                explode_distribution(&self.reroll_distribution(&faces), explodes_on, |face, _| Ok(self.score(face)), budget)
+            },
+            Some(Explode::Penetrating(compare_point)) => {
+                let explodes_on = |face| explodes(compare_point, max_face, face);
+                let penetrate = |face, extra| self.penetrate(face, extra);
+                explode_distribution(&self.reroll_distribution(&faces), explodes_on, penetrate, budget)
+            },
+            _ => self.die_distribution()?.map(|value| Ok(self.score(value))),
+        }
+    }
+
+    /// The chance of each face a die drawn from `faces` finishes on, once it has been rerolled.
+    fn reroll_distribution(&self, faces: &Distribution) -> Distribution {
+        let always = |face: i64| self.rerolls.iter().any(|reroll| match *reroll {
+            Reroll::Always(target) => target.matches(face),
+            Reroll::Once(_) => false,
+        });
+        let once = |face: i64| self.rerolls.iter().any(|reroll| match *reroll {
+            Reroll::Always(_) => false,
+            Reroll::Once(target) => target.matches(face),
+        });
+
+        let settles = faces.restrict(|face| !always(face));
+        if settles.is_empty() {
+            // a die that rerolls on every face keeps its last face once the budget runs out
+            return faces.clone();
+        }
+        // after rerolling once, a die only keeps rerolling on faces it always rerolls
+        let rerolled = settles.normalized().scale(settles.restrict(once).mass());
+        settles.restrict(|face| !once(face))
+            .mix(&rerolled)
+            .expect("rerolled faces are within the original faces")
+            .normalized()
+    }
+
+    pub fn roll_dice(&self, source: &mut dyn DieSource, extra: &mut u64) -> Result<i64, Error> {
+        self.roll(source, extra).map(|result| result.total)
+    }
+
+    /// Rolls the dice, taking the face of every die from `source`. Rerolls and explosions may add up to
+    /// `extra` dice, which are shared with the rest of the expression, and what they use is taken from it.
+    pub fn roll(&self, source: &mut dyn DieSource, extra: &mut u64) -> Result<DiceResult, Error> {
+        source.begin(&self.notation);
+        let mut warnings = Vec::new();
+        let results = match self.die_type {
+            DieType::Custom => self.roll_custom_dice(source, extra, &mut warnings),
+            DieType::Fate => self.roll_fate_dice(source, extra, &mut warnings),
+            DieType::Normal => self.roll_normal_dice(self.sides, source, extra, &mut warnings),
+            DieType::Percentile => self.roll_normal_dice(100, source, extra, &mut warnings),
+        }?;
+
+        let kept = match self.take {
+            Some(take) => take.kept(&results),
+            None => vec![true; results.len()],
         };
 
-        if let Some(take) = self.take {
-            let limit_take = |take_num: u64| -> u64 {
-                if take_num > self.count {
-                    self.count
-                } else {
-                    take_num
-                }
-            };
-
-            results.sort_by(|a, b| a.cmp(b)); // sort by ascending
-            results = match take {
-                Take::KeepHighest(kh) => results[..limit_take(kh) as usize].to_vec(),
-                Take::DropLowest(dl) => results[limit_take(dl) as usize..].to_vec(),
-            };
-        }
-
-        if let Some(target_roll) = self.target_roll {
-            let success_count = match target_roll {
-                TargetRoll::GT(target_number) => results.iter().filter(|&roll| *roll > target_number).count(),
-                TargetRoll::GTE(target_number) => results.iter().filter(|&roll| *roll >= target_number).count(),
-                TargetRoll::LT(target_number) => results.iter().filter(|&roll| *roll < target_number).count(),
-                TargetRoll::LTE(target_number) => results.iter().filter(|&roll| *roll <= target_number).count(),
-                TargetRoll::EQ(target_number) => results.iter().filter(|&roll| *roll == target_number).count(),
-            };
-            success_count as u64
+        let dice = results.iter().zip(kept).map(|(&value, kept)| Die {
+            value,
+            dropped: !kept,
+            success: kept && self.target_roll.is_some_and(|target_roll| target_roll.matches(value)),
+        }).collect::<Vec<Die>>();
+
+        let total = if self.target_roll.is_some() {
+            dice.iter().filter(|die| die.success).count() as i64
         } else {
-            results.iter().fold(0, |acc, x| acc + x)
+            dice.iter()
+                .filter(|die| !die.dropped)
+                .try_fold(0i64, |acc, die| acc.checked_add(die.value))
+                .ok_or(Error::Overflow)?
+        };
+
+        Ok(DiceResult {
+            die_type: self.die_type,
+            dice,
+            counts_successes: self.target_roll.is_some(),
+            total,
+            warnings,
+        })
+    }
+
+    fn roll_normal_dice(&self, sides: u64, source: &mut dyn DieSource, extra: &mut u64, warnings: &mut Vec<Warning>) -> Result<Vec<i64>, Error> {
+        if sides == 0 {
+            // zero-sided dice will always roll zero
+            return Ok(vec![0; self.count as usize]);
         }
+
+        let faces = Faces::Between(1, sides as i64);
+        self.roll_each(|| source.draw(&faces), sides as i64, extra, warnings)
     }
 
-    fn roll_custom_dice(&self) -> i64 {
+    fn roll_fate_dice(&self, source: &mut dyn DieSource, extra: &mut u64, warnings: &mut Vec<Warning>) -> Result<Vec<i64>, Error> {
+        // Fate dice have two blank faces, two minus faces and two plus faces
+        let faces = Faces::Between(-1, 1);
+        self.roll_each(|| source.draw(&faces), 1, extra, warnings)
+    }
+
+    fn roll_custom_dice(&self, source: &mut dyn DieSource, extra: &mut u64, warnings: &mut Vec<Warning>) -> Result<Vec<i64>, Error> {
         if self.custom_sides.is_empty() {
-            return 0;
+            // as with zero-sided dice, a die with no faces always rolls zero
+            return Ok(vec![0; self.count as usize]);
         }
 
-        let custom_sides = if self.custom_sides.len() > MAX_CUSTOM_SIDES {
-            &self.custom_sides[..MAX_CUSTOM_SIDES]
+        let custom_sides = self.custom_faces();
+        let max_face = *custom_sides.iter().max().unwrap();
+
+        let faces = Faces::Listed(custom_sides);
+        self.roll_each(|| source.draw(&faces), max_face, extra, warnings)
+    }
+
+    fn custom_faces(&self) -> &[i64] {
+        let max = self.limits.custom_sides.min(self.custom_sides.len() as u64);
+        &self.custom_sides[..max as usize]
+    }
+
+    /// Rolls each of the dice through `draw`, applying rerolls and explosions, and noting a warning for
+    /// each budget that runs out.
+    fn roll_each<F>(&self, draw: F, max_face: i64, extra: &mut u64, warnings: &mut Vec<Warning>) -> Result<Vec<i64>, Error>
+        where F: FnMut() -> i64
+    {
+        // the roll runs out of dice at whichever of its own limit and the expression's comes first
+        let own = self.limits.dice.saturating_sub(self.count);
+        let (extra_rolls, limit) = if own <= *extra {
+            (own, Limit::Dice)
         } else {
-            &self.custom_sides[..]
+            (*extra, Limit::TotalDice)
+        };
+        let mut dice = Dice {
+            draw,
+            rerolls: &self.rerolls,
+            limits: &self.limits,
+            extra_rolls,
+            limit,
+            explosions: self.limits.explosions,
+            warnings,
         };
 
-        use rand::seq::SliceRandom;
-        let mut rng = thread_rng();
-        (0..self.count).map(|_| custom_sides.choose(&mut rng).unwrap()).fold(0, |acc, x| acc + *x)
+        let mut results = Vec::with_capacity(self.count as usize);
+        for _ in 0..self.count {
+            match self.explode {
+                Some(explode) => explode_die(explode, max_face, &mut dice, &mut results)?,
+                None => results.push(dice.roll()?),
+            }
+        }
+        *extra -= extra_rolls - dice.extra_rolls;
+        Ok(results)
+    }
+}
+
+/// The faces a single die can land on.
+pub enum Faces<'a> {
+    /// Every number from the first to the second inclusive.
+    Between(i64, i64),
+    /// Each of a custom die's faces.
+    Listed(&'a [i64]),
+}
+
+impl<'a> Faces<'a> {
+    pub fn contains(&self, face: i64) -> bool {
+        match *self {
+            Faces::Between(low, high) => low <= face && face <= high,
+            Faces::Listed(faces) => faces.contains(&face),
+        }
+    }
+}
+
+/// Decides the face of every die drawn while rolling.
+pub trait DieSource {
+    /// Called before drawing the dice of each die code, with the die code as it was written.
+    fn begin(&mut self, notation: &str);
+
+    /// Draws a single die with the given faces.
+    fn draw(&mut self, faces: &Faces) -> i64;
+}
+
+/// Any random number generator draws dice at random.
+impl<R: Rng + ?Sized> DieSource for R {
+    fn begin(&mut self, _: &str) {}
+
+    fn draw(&mut self, faces: &Faces) -> i64 {
+        match *faces {
+            Faces::Between(low, high) => Uniform::new_inclusive(low, high).sample(self),
+            Faces::Listed(faces) => *faces.choose(self).unwrap(),
+        }
+    }
+}
+
+/// Draws individual dice for a `Roll`, applying any rerolls. Rerolls and explosions share a budget of
+/// `extra_rolls` on top of the initial dice, set by `limit`, so that no roll goes over its `Limits`.
+struct Dice<'a, F> {
+    draw: F,
+    rerolls: &'a [Reroll],
+    limits: &'a Limits,
+    extra_rolls: u64,
+    limit: Limit,
+    explosions: u64,
+    warnings: &'a mut Vec<Warning>,
+}
+
+impl<'a, F> Dice<'a, F> where F: FnMut() -> i64 {
+    /// Takes one roll from the remaining budget, returning false if it has been used up and the limits
+    /// allow the roll to carry on without it.
+    fn spend(&mut self) -> Result<bool, Error> {
+        if self.extra_rolls > 0 {
+            self.extra_rolls -= 1;
+            Ok(true)
+        } else {
+            self.run_out(self.limit)
+        }
+    }
+
+    /// Takes one explosion, and a roll for it, from the remaining budget.
+    fn explode(&mut self) -> Result<bool, Error> {
+        if self.explosions > 0 {
+            self.explosions -= 1;
+            self.spend()
+        } else {
+            self.run_out(Limit::Explosions)
+        }
+    }
+
+    /// Fails with `LimitExceeded` once `limit` runs out, unless the limits allow the roll to carry on, in
+    /// which case a warning is noted the first time.
+    fn run_out(&mut self, limit: Limit) -> Result<bool, Error> {
+        self.limits.exceeded(limit)?;
+        let warning = Warning::Clamped { limit, max: self.limits.max(limit) };
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+        Ok(false)
+    }
+
+    fn roll(&mut self) -> Result<i64, Error> {
+        let mut roll = (self.draw)();
+        let mut rerolled_once = false;
+        loop {
+            let always = self.rerolls.iter().any(|reroll| match *reroll {
+                Reroll::Always(target) => target.matches(roll),
+                Reroll::Once(_) => false,
+            });
+            let once = !rerolled_once && self.rerolls.iter().any(|reroll| match *reroll {
+                Reroll::Always(_) => false,
+                Reroll::Once(target) => target.matches(roll),
+            });
+            if !(always || once) || !self.spend()? {
+                return Ok(roll);
+            }
+            rerolled_once |= once;
+            roll = (self.draw)();
+        }
+    }
+}
+
+/// Whether a die showing `face` explodes. Without a compare point, a die explodes on its highest face.
+fn explodes(compare_point: Option<TargetRoll>, max_face: i64, face: i64) -> bool {
+    match compare_point {
+        Some(compare_point) => compare_point.matches(face),
+        None => face == max_face,
+    }
+}
+
+/// Works out the lowest and highest totals of `count` dice drawn from `draw` which may explode `budget`
+/// times between them, with each die scored by `value(face, extra)`, where `extra` is false only for the
+/// first die of each.
+///
+/// Their distribution stops following explosions once another becomes negligible, so the totals are found
+/// from the faces instead. While the budget lasts, every die stops on a face that doesn't explode, and once
+/// it runs out any face stops. Each total is linear in the number of dice that explode and the number of
+/// explosions, so it is enough to try the fewest and most of each.
+fn explosion_bounds<E, V>(draw: &Distribution, explodes: E, value: V, count: u64, budget: u64) -> Result<(i64, i64), Error>
+    where E: Fn(i64) -> bool,
+          V: Fn(i64, bool) -> Result<i64, Error>
+{
+    // the lowest and highest values of the faces which explode (or don't), or of any face if `explode` is None.
+    // `map_or` is used rather than `is_none_or`, which needs Rust 1.82
+    #[allow(clippy::unnecessary_map_or)]
+    let range = |explode: Option<bool>, extra: bool| -> Result<Option<(i128, i128)>, Error> {
+        let mut range: Option<(i128, i128)> = None;
+        for (face, _) in draw.iter().filter(|&(face, _)| explode.map_or(true, |explode| explodes(face) == explode)) {
+            let value = i128::from(value(face, extra)?);
+            range = Some(range.map_or((value, value), |(min, max)| (min.min(value), max.max(value))));
+        }
+        Ok(range)
+    };
+    let (first_explodes, extra_explodes) = (range(Some(true), false)?, range(Some(true), true)?);
+    let (first_stops, extra_stops) = (range(Some(false), false)?, range(Some(false), true)?);
+    let (first_any, extra_any) = (range(None, false)?, range(None, true)?);
+
+    let count = i128::from(count);
+    let budget = if first_explodes.is_some() { i128::from(budget) } else { 0 };
+    let (mut min, mut max): (Option<i128>, Option<i128>) = (None, None);
+    let mut total = |dice: &[(i128, Option<(i128, i128)>)]| {
+        if dice.iter().any(|&(number, range)| number > 0 && range.is_none()) {
+            return;
+        }
+        let (low, high) = dice.iter()
+            .filter(|&&(number, _)| number > 0)
+            .fold((0, 0), |(low, high), &(number, range)| {
+                let (min, max) = range.unwrap();
+                (low + number * min, high + number * max)
+            });
+        min = Some(min.map_or(low, |min| min.min(low)));
+        max = Some(max.map_or(high, |max| max.max(high)));
+    };
+    for &chains in &[0, 1, count.min(budget - 1), count.min(budget)] {
+        if chains < 0 || chains > count.min(budget) {
+            continue;
+        }
+        for &explosions in &[chains, budget - 1] {
+            if chains <= explosions && explosions < budget && (chains > 0 || explosions == 0) {
+                total(&[(chains, first_explodes), (explosions - chains, extra_explodes), (chains, extra_stops), (count - chains, first_stops)]);
+            }
+        }
+        if chains > 0 || budget == 0 {
+            total(&[(chains, first_explodes), (budget - chains, extra_explodes), (chains, extra_any), (count - chains, first_any)]);
+        }
+    }
+
+    let fit = |total: Option<i128>| i64::try_from(total.expect("the budget running out is always possible")).map_err(|_| Error::Overflow);
+    Ok((fit(min)?, fit(max)?))
+}
+
+/// Works out the distribution of the total of a single die and the dice it explodes into, each drawn
+/// from `draw` and scored by `value(face, extra)`, where `extra` is false only for the first die.
+fn explode_distribution<E, V>(draw: &Distribution, explodes: E, value: V, budget: u64) -> Result<Distribution, Error>
+    where E: Fn(i64) -> bool,
+          V: Fn(i64, bool) -> Result<i64, Error>
+{
+    let (stops, explodes) = (draw.restrict(|face| !explodes(face)), draw.restrict(explodes));
+
+    let mut total = Distribution::empty();
+    // the chance of each running total while the die is still exploding
+    let mut exploding = Distribution::constant(0);
+    for rolls in 0..=budget {
+        let extra = rolls > 0;
+        total = total.mix(&exploding.convolve(&stops.map(|face| value(face, extra))?)?)?;
+        exploding = exploding.convolve(&explodes.map(|face| value(face, extra))?)?;
+        if exploding.mass() < NEGLIGIBLE {
+            break;
+        }
+    }
+    total.mix(&exploding)
+}
+
+/// Rolls a single die, then keeps rolling for as long as it explodes and the remaining roll budget
+/// allows, pushing the resulting dice onto `results`.
+fn explode_die<F>(explode: Explode, max_face: i64, dice: &mut Dice<F>, results: &mut Vec<i64>) -> Result<(), Error>
+    where F: FnMut() -> i64
+{
+    let mut roll = dice.roll()?;
+    match explode {
+        Explode::Exploding(compare_point) => {
+            results.push(roll);
+            while explodes(compare_point, max_face, roll) && dice.explode()? {
+                roll = dice.roll()?;
+                results.push(roll);
+            }
+        },
+        Explode::Compounding(compare_point) => {
+            let mut total = roll;
+            while explodes(compare_point, max_face, roll) && dice.explode()? {
+                roll = dice.roll()?;
+                total = total.checked_add(roll).ok_or(Error::Overflow)?;
+            }
+            results.push(total);
+        },
+        Explode::Penetrating(compare_point) => {
+            results.push(roll);
+            // Each penetrating die is worth one less, but still explodes on its natural face
+            while explodes(compare_point, max_face, roll) && dice.explode()? {
+                roll = dice.roll()?;
+                results.push(roll.checked_sub(1).ok_or(Error::Overflow)?);
+            }
+        },
     }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use std::cell::RefCell;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    use super::Roll;
+    use error::Error;
+    use limits::Limits;
+
+    thread_local! {
+        // Each test runs on its own thread, so each rolls the same dice every time it is run
+        static RNG: RefCell<ChaCha20Rng> = RefCell::new(ChaCha20Rng::seed_from_u64(2016));
+    }
+
+    /// Rolls `roll` with the default limits.
+    fn roll(roll: &Roll) -> Result<i64, Error> {
+        RNG.with(|rng| roll.roll_dice(&mut *rng.borrow_mut(), &mut Limits::default().total_dice))
+    }
+
     mod normal {
-        use super::super::{MAX_ROLLS, MAX_SIDES, Roll, TargetRoll};
+        use super::super::{Explode, Limits, Reroll, Roll, TargetRoll};
+        use super::roll;
 
         #[test]
         fn zero_d_zero() {
-            assert_eq!(Roll::new().count(0).sides(0).roll_dice(), 0);
+            assert_eq!(roll(Roll::new().count(0).sides(0)), Ok(0));
         }
 
         #[test]
         fn one_d_zero() {
-            assert_eq!(Roll::new().count(1).sides(0).roll_dice(), 0);
+            assert_eq!(roll(Roll::new().count(1).sides(0)), Ok(0));
         }
 
         #[test]
         fn zero_d_one() {
-            assert_eq!(Roll::new().count(0).sides(1).roll_dice(), 0);
+            assert_eq!(roll(Roll::new().count(0).sides(1)), Ok(0));
         }
 
         #[test]
         fn x_d_one() {
             for x in 1..100 {
-                assert_eq!(Roll::new().count(x).sides(1).roll_dice(), x as i64);
+                assert_eq!(roll(Roll::new().count(x).sides(1)), Ok(x as i64));
             }
         }
 
         #[test]
         fn one_d_x() {
             for x in 1..100 {
-                let roll = Roll::new().count(1).sides(x).roll_dice();
+                let roll = roll(Roll::new().count(1).sides(x)).unwrap();
                 assert!(1 <= roll && roll <= x as i64);
             }
         }
 
         #[test]
         fn max() {
-            let roll = Roll::new().count(u64::max_value()).sides(u64::max_value()).roll_dice();
-            let max = MAX_ROLLS * MAX_SIDES;
+            let roll = roll(Roll::new().count(u64::MAX).sides(u64::MAX)).unwrap();
+            let max = Limits::default().dice * Limits::default().sides;
             assert!(1 <= roll && roll <= max as i64);
         }
 
         #[test]
         fn keep_two() {
-            assert_eq!(Roll::new().count(5).sides(1).keep_highest(2).roll_dice(), 2);
+            assert_eq!(roll(Roll::new().count(5).sides(1).keep_highest(2)), Ok(2));
         }
 
         #[test]
         fn keep_more() {
-            assert_eq!(Roll::new().count(5).sides(1).keep_highest(6).roll_dice(), 5);
+            assert_eq!(roll(Roll::new().count(5).sides(1).keep_highest(6)), Ok(5));
         }
 
 
         #[test]
         fn keep_zero() {
-            assert_eq!(Roll::new().count(5).sides(1).keep_highest(0).roll_dice(), 0);
+            assert_eq!(roll(Roll::new().count(5).sides(1).keep_highest(0)), Ok(0));
         }
 
         #[test]
         fn keep_max() {
-            assert_eq!(Roll::new().count(5).sides(1).keep_highest(u64::max_value()).roll_dice(), 5);
+            assert_eq!(roll(Roll::new().count(5).sides(1).keep_highest(u64::MAX)), Ok(5));
+        }
+
+        #[test]
+        fn keep_lowest_two() {
+            assert_eq!(roll(Roll::new().count(5).sides(1).keep_lowest(2)), Ok(2));
+            assert_eq!(roll(Roll::new().count(5).sides(1).keep_lowest(u64::MAX)), Ok(5));
+        }
+
+        #[test]
+        fn drop_highest_two() {
+            assert_eq!(roll(Roll::new().count(5).sides(1).drop_highest(2)), Ok(3));
+            assert_eq!(roll(Roll::new().count(5).sides(1).drop_highest(u64::MAX)), Ok(0));
         }
 
         #[test]
         fn drop_none() {
-            assert_eq!(Roll::new().count(0).drop_lowest(0).roll_dice(), 0);
+            assert_eq!(roll(Roll::new().count(0).drop_lowest(0)), Ok(0));
         }
 
         #[test]
         fn drop_two() {
-            assert_eq!(Roll::new().count(5).sides(1).drop_lowest(2).roll_dice(), 3);
+            assert_eq!(roll(Roll::new().count(5).sides(1).drop_lowest(2)), Ok(3));
         }
 
         #[test]
         fn drop_more() {
-            assert_eq!(Roll::new().count(5).sides(1).drop_lowest(6).roll_dice(), 0);
+            assert_eq!(roll(Roll::new().count(5).sides(1).drop_lowest(6)), Ok(0));
         }
 
 
         #[test]
         fn drop_zero() {
-            assert_eq!(Roll::new().count(5).sides(1).drop_lowest(0).roll_dice(), 5);
+            assert_eq!(roll(Roll::new().count(5).sides(1).drop_lowest(0)), Ok(5));
         }
 
         #[test]
         fn drop_max() {
-            assert_eq!(Roll::new().count(5).sides(1).drop_lowest(u64::max_value()).roll_dice(), 0);
+            assert_eq!(roll(Roll::new().count(5).sides(1).drop_lowest(u64::MAX)), Ok(0));
+        }
+
+        #[test]
+        fn explode_max() {
+            assert_eq!(roll(Roll::new().count(1).sides(1).explode(Explode::Exploding(None))), Ok(Limits::default().dice as i64));
+            assert_eq!(roll(Roll::new().count(Limits::default().dice).sides(1).explode(Explode::Exploding(None))), Ok(Limits::default().dice as i64));
+        }
+
+        #[test]
+        fn explode_compare_point() {
+            assert_eq!(roll(Roll::new().count(10).sides(1).explode(Explode::Exploding(Some(TargetRoll::GT(1))))), Ok(10));
+            assert_eq!(roll(Roll::new().count(10).sides(1).explode(Explode::Exploding(Some(TargetRoll::EQ(1))))), Ok(Limits::default().dice as i64));
+        }
+
+        #[test]
+        fn explode_zero_sides() {
+            assert_eq!(roll(Roll::new().count(10).sides(0).explode(Explode::Exploding(None))), Ok(0));
+        }
+
+        #[test]
+        fn explode_then_take() {
+            assert_eq!(roll(Roll::new().count(1).sides(1).explode(Explode::Exploding(None)).drop_lowest(10)), Ok(Limits::default().dice as i64 - 10));
+            assert_eq!(roll(Roll::new().count(1).sides(1).explode(Explode::Exploding(None)).target_roll(TargetRoll::EQ(1))), Ok(Limits::default().dice as i64));
+        }
+
+        #[test]
+        fn compound_max() {
+            assert_eq!(roll(Roll::new().count(1).sides(1).explode(Explode::Compounding(None))), Ok(Limits::default().dice as i64));
+            assert_eq!(roll(Roll::new().count(2).sides(1).explode(Explode::Compounding(None)).drop_lowest(1)), Ok(Limits::default().dice as i64 - 1));
+        }
+
+        #[test]
+        fn penetrate_max() {
+            assert_eq!(roll(Roll::new().count(1).sides(1).explode(Explode::Penetrating(None))), Ok(1));
+            assert_eq!(roll(Roll::new().count(1).sides(1).explode(Explode::Penetrating(None)).target_roll(TargetRoll::EQ(0))), Ok(Limits::default().dice as i64 - 1));
+        }
+
+        #[test]
+        fn reroll_always() {
+            assert_eq!(roll(Roll::new().count(100).sides(2).reroll(Reroll::Always(TargetRoll::LT(2)))), Ok(200));
+            assert_eq!(roll(Roll::new().count(100).sides(2).reroll(Reroll::Always(TargetRoll::GT(2))).target_roll(TargetRoll::GT(2))), Ok(0));
+        }
+
+        #[test]
+        fn reroll_once() {
+            let total = roll(Roll::new().count(100).sides(2).reroll(Reroll::Once(TargetRoll::EQ(1)))).unwrap();
+            assert!((100..=200).contains(&total));
+            assert_eq!(roll(Roll::new().count(5).sides(1).reroll(Reroll::Once(TargetRoll::EQ(1)))), Ok(5));
+        }
+
+        #[test]
+        fn reroll_shares_budget() {
+            assert_eq!(roll(Roll::new().count(1).sides(1).reroll(Reroll::Always(TargetRoll::EQ(1)))), Ok(1));
+            assert_eq!(roll(Roll::new().count(1).sides(1).reroll(Reroll::Always(TargetRoll::EQ(1))).explode(Explode::Exploding(None))), Ok(1));
         }
 
         #[test]
         fn target_gt() {
-            assert_eq!(Roll::new().count(100).sides(1).target_roll(TargetRoll::GT(1)).roll_dice(), 0);
-            assert_eq!(Roll::new().count(100).sides(100).target_roll(TargetRoll::GT(0)).roll_dice(), 100);
-            assert_eq!(Roll::new().count(u64::max_value()).sides(u64::max_value()).target_roll(TargetRoll::GT(u64::max_value())).roll_dice(), 0);
+            assert_eq!(roll(Roll::new().count(100).sides(1).target_roll(TargetRoll::GT(1))), Ok(0));
+            assert_eq!(roll(Roll::new().count(100).sides(100).target_roll(TargetRoll::GT(0))), Ok(100));
+            assert_eq!(roll(Roll::new().count(u64::MAX).sides(u64::MAX).target_roll(TargetRoll::GT(i64::MAX))), Ok(0));
         }
 
         #[test]
         fn target_gte() {
-            assert_eq!(Roll::new().count(100).sides(1).target_roll(TargetRoll::GTE(1)).roll_dice(), 100);
-            assert_eq!(Roll::new().count(100).sides(100).target_roll(TargetRoll::GTE(0)).roll_dice(), 100);
-            assert_eq!(Roll::new().count(u64::max_value()).sides(u64::max_value()).target_roll(TargetRoll::GTE(u64::max_value())).roll_dice(), 0);
+            assert_eq!(roll(Roll::new().count(100).sides(1).target_roll(TargetRoll::GTE(1))), Ok(100));
+            assert_eq!(roll(Roll::new().count(100).sides(100).target_roll(TargetRoll::GTE(0))), Ok(100));
+            assert_eq!(roll(Roll::new().count(u64::MAX).sides(u64::MAX).target_roll(TargetRoll::GTE(i64::MAX))), Ok(0));
 
-            assert_eq!(Roll::new().count(10).sides(0).target_roll(TargetRoll::GTE(0)).roll_dice(), 10);
-            assert_eq!(Roll::new().count(0).sides(10).target_roll(TargetRoll::GTE(0)).roll_dice(), 0);
-            assert_eq!(Roll::new().count(100).sides(1).target_roll(TargetRoll::GTE(1)).roll_dice(), 100);
+            assert_eq!(roll(Roll::new().count(10).sides(0).target_roll(TargetRoll::GTE(0))), Ok(10));
+            assert_eq!(roll(Roll::new().count(0).sides(10).target_roll(TargetRoll::GTE(0))), Ok(0));
+            assert_eq!(roll(Roll::new().count(100).sides(1).target_roll(TargetRoll::GTE(1))), Ok(100));
         }
 
         #[test]
         fn target_lt() {
-            assert_eq!(Roll::new().count(100).sides(100).target_roll(TargetRoll::LT(101)).roll_dice(), 100);
-            assert_eq!(Roll::new().count(100).sides(0).target_roll(TargetRoll::LT(1)).roll_dice(), 100);
-            assert_eq!(Roll::new().count(100).sides(1).target_roll(TargetRoll::LT(1)).roll_dice(), 0);
-            assert_eq!(Roll::new().count(0).sides(100).target_roll(TargetRoll::LT(1)).roll_dice(), 0);
-            assert_eq!(Roll::new().count(100).sides(0).target_roll(TargetRoll::LT(0)).roll_dice(), 0);
+            assert_eq!(roll(Roll::new().count(100).sides(100).target_roll(TargetRoll::LT(101))), Ok(100));
+            assert_eq!(roll(Roll::new().count(100).sides(0).target_roll(TargetRoll::LT(1))), Ok(100));
+            assert_eq!(roll(Roll::new().count(100).sides(1).target_roll(TargetRoll::LT(1))), Ok(0));
+            assert_eq!(roll(Roll::new().count(0).sides(100).target_roll(TargetRoll::LT(1))), Ok(0));
+            assert_eq!(roll(Roll::new().count(100).sides(0).target_roll(TargetRoll::LT(0))), Ok(0));
         }
 
         #[test]
         fn target_lte() {
-            assert_eq!(Roll::new().count(100).sides(100).target_roll(TargetRoll::LTE(100)).roll_dice(), 100);
-            assert_eq!(Roll::new().count(100).sides(0).target_roll(TargetRoll::LTE(0)).roll_dice(), 100);
-            assert_eq!(Roll::new().count(100).sides(1).target_roll(TargetRoll::LTE(1)).roll_dice(), 100);
-            assert_eq!(Roll::new().count(0).sides(100).target_roll(TargetRoll::LTE(1)).roll_dice(), 0);
+            assert_eq!(roll(Roll::new().count(100).sides(100).target_roll(TargetRoll::LTE(100))), Ok(100));
+            assert_eq!(roll(Roll::new().count(100).sides(0).target_roll(TargetRoll::LTE(0))), Ok(100));
+            assert_eq!(roll(Roll::new().count(100).sides(1).target_roll(TargetRoll::LTE(1))), Ok(100));
+            assert_eq!(roll(Roll::new().count(0).sides(100).target_roll(TargetRoll::LTE(1))), Ok(0));
         }
 
         #[test]
         fn target_eq() {
-            assert_eq!(Roll::new().count(100).sides(100).target_roll(TargetRoll::EQ(0)).roll_dice(), 0);
-            assert_eq!(Roll::new().count(100).sides(0).target_roll(TargetRoll::EQ(0)).roll_dice(), 100);
-            assert_eq!(Roll::new().count(100).sides(1).target_roll(TargetRoll::EQ(1)).roll_dice(), 100);
-            assert_eq!(Roll::new().count(0).sides(100).target_roll(TargetRoll::EQ(0)).roll_dice(), 0);
+            assert_eq!(roll(Roll::new().count(100).sides(100).target_roll(TargetRoll::EQ(0))), Ok(0));
+            assert_eq!(roll(Roll::new().count(100).sides(0).target_roll(TargetRoll::EQ(0))), Ok(100));
+            assert_eq!(roll(Roll::new().count(100).sides(1).target_roll(TargetRoll::EQ(1))), Ok(100));
+            assert_eq!(roll(Roll::new().count(0).sides(100).target_roll(TargetRoll::EQ(0))), Ok(0));
+        }
+    }
+
+    mod fate {
+        use super::super::{DieType, Explode, Limits, Roll, TargetRoll};
+        use super::roll;
+
+        #[test]
+        fn one_d_fate() {
+            for _ in 0..100 {
+                let roll = roll(Roll::new().die_type(DieType::Fate).count(1)).unwrap();
+                assert!((-1..=1).contains(&roll));
+            }
+        }
+
+        #[test]
+        fn many_d_fate() {
+            let total = roll(Roll::new().die_type(DieType::Fate).count(100)).unwrap();
+            assert!((-100..=100).contains(&total));
+            assert_eq!(roll(Roll::new().die_type(DieType::Fate).count(100).target_roll(TargetRoll::LTE(1))), Ok(100));
+        }
+
+        #[test]
+        fn take() {
+            assert_eq!(roll(Roll::new().die_type(DieType::Fate).count(Limits::default().dice).keep_highest(1)), Ok(1));
+            assert_eq!(roll(Roll::new().die_type(DieType::Fate).count(Limits::default().dice).keep_lowest(1)), Ok(-1));
+        }
+
+        #[test]
+        fn explode() {
+            let roll = roll(Roll::new().die_type(DieType::Fate).count(1).explode(Explode::Exploding(Some(TargetRoll::GTE(-1)))).target_roll(TargetRoll::LTE(1))).unwrap();
+            assert_eq!(roll, Limits::default().dice as i64);
+        }
+    }
+
+    mod percentile {
+        use super::super::{DieType, Limits, Roll};
+        use super::roll;
+
+        #[test]
+        fn one_d_percentile() {
+            for _ in 0..100 {
+                let roll = roll(Roll::new().die_type(DieType::Percentile).count(1)).unwrap();
+                assert!((1..=100).contains(&roll));
+            }
+        }
+
+        #[test]
+        fn ignores_sides() {
+            let roll = roll(Roll::new().die_type(DieType::Percentile).count(1).sides(1)).unwrap();
+            assert!((1..=100).contains(&roll));
+        }
+
+        #[test]
+        fn take() {
+            assert_eq!(roll(Roll::new().die_type(DieType::Percentile).count(Limits::default().dice).keep_highest(1)), Ok(100));
+        }
+    }
+
+    mod take {
+        use super::super::Take;
+
+        fn take(take: Take, results: &[i64]) -> Vec<i64> {
+            let mut kept = results.iter().zip(take.kept(results))
+                .filter(|&(_, kept)| kept)
+                .map(|(&result, _)| result)
+                .collect::<Vec<i64>>();
+            kept.sort();
+            kept
+        }
+
+        #[test]
+        fn keeps_rolled_order() {
+            assert_eq!(Take::KeepHighest(2).kept(&[3, 6, 1, 5]), vec![false, true, false, true]);
+            assert_eq!(Take::KeepLowest(1).kept(&[3, 6, 1, 5]), vec![false, false, true, false]);
+            assert_eq!(Take::DropHighest(1).kept(&[3, 6, 1, 5]), vec![true, false, true, true]);
+            assert_eq!(Take::DropLowest(1).kept(&[2, 5, 3, 2]), vec![false, true, true, true]);
+        }
+
+        #[test]
+        fn keep_highest() {
+            assert_eq!(take(Take::KeepHighest(2), &[3, 6, 1, 5]), vec![5, 6]);
+            assert_eq!(take(Take::KeepHighest(0), &[3, 6, 1, 5]), vec![]);
+            assert_eq!(take(Take::KeepHighest(5), &[3, 6, 1, 5]), vec![1, 3, 5, 6]);
+        }
+
+        #[test]
+        fn keep_lowest() {
+            assert_eq!(take(Take::KeepLowest(1), &[17, 4]), vec![4]);
+            assert_eq!(take(Take::KeepLowest(0), &[17, 4]), vec![]);
+            assert_eq!(take(Take::KeepLowest(5), &[17, 4]), vec![4, 17]);
+        }
+
+        #[test]
+        fn drop_highest() {
+            assert_eq!(take(Take::DropHighest(1), &[2, 5, 3, 2]), vec![2, 2, 3]);
+            assert_eq!(take(Take::DropHighest(0), &[2, 5, 3, 2]), vec![2, 2, 3, 5]);
+            assert_eq!(take(Take::DropHighest(5), &[2, 5, 3, 2]), vec![]);
+        }
+
+        #[test]
+        fn drop_lowest() {
+            assert_eq!(take(Take::DropLowest(1), &[2, 5, 3, 2]), vec![2, 3, 5]);
+            assert_eq!(take(Take::DropLowest(0), &[2, 5, 3, 2]), vec![2, 2, 3, 5]);
+            assert_eq!(take(Take::DropLowest(5), &[2, 5, 3, 2]), vec![]);
         }
     }
 
     mod custom {
-        use super::super::{MAX_ROLLS, MAX_CUSTOM_SIDES, DieType, Roll};
+        use super::super::{DieType, Error, Explode, Limits, Reroll, Roll, TargetRoll};
+        use super::roll;
 
         #[test]
         fn zero_d_empty() {
-            assert_eq!(Roll::new().die_type(DieType::Custom).add_custom_sides(&[]).roll_dice(), 0);
+            assert_eq!(roll(Roll::new().die_type(DieType::Custom).add_custom_sides(&[])), Ok(0));
         }
 
         #[test]
         fn one_d_empty() {
-            assert_eq!(Roll::new().die_type(DieType::Custom).count(1).roll_dice(), 0);
+            assert_eq!(roll(Roll::new().die_type(DieType::Custom).count(1)), Ok(0));
         }
 
         #[test]
         fn zero_d_one() {
-            assert_eq!(Roll::new().die_type(DieType::Custom).count(0).add_custom_sides(&[42]).roll_dice(), 0);
+            assert_eq!(roll(Roll::new().die_type(DieType::Custom).count(0).add_custom_sides(&[42])), Ok(0));
         }
 
         #[test]
         fn one_d_one() {
-            assert_eq!(Roll::new().die_type(DieType::Custom).count(1).add_custom_sides(&[42]).roll_dice(), 42);
+            assert_eq!(roll(Roll::new().die_type(DieType::Custom).count(1).add_custom_sides(&[42])), Ok(42));
         }
 
         #[test]
         fn one_d_many() {
             let sequence: Vec<i64> = (-25..25).collect();
-            let roll = Roll::new().die_type(DieType::Custom).count(1).add_custom_sides(&sequence[..]).roll_dice();
-            assert!(-25 <= roll && roll <= 25);
+            let roll = roll(Roll::new().die_type(DieType::Custom).count(1).add_custom_sides(&sequence[..])).unwrap();
+            assert!((-25..=25).contains(&roll));
         }
 
         #[test]
         fn many_d_one() {
-            assert_eq!(Roll::new().die_type(DieType::Custom).count(100).add_custom_sides(&[42]).roll_dice(), 100*42);
+            assert_eq!(roll(Roll::new().die_type(DieType::Custom).count(100).add_custom_sides(&[42])), Ok(100*42));
+        }
+
+        #[test]
+        fn keep_and_drop() {
+            assert_eq!(roll(Roll::new().die_type(DieType::Custom).count(5).add_custom_sides(&[-3]).keep_highest(2)), Ok(-6));
+            assert_eq!(roll(Roll::new().die_type(DieType::Custom).count(5).add_custom_sides(&[-3]).drop_lowest(2)), Ok(-9));
+
+            let roll = roll(Roll::new().die_type(DieType::Custom).count(4).add_custom_sides(&[1, 2, 3]).keep_highest(2)).unwrap();
+            assert!((2..=6).contains(&roll));
+        }
+
+        #[test]
+        fn target_signed() {
+            assert_eq!(roll(Roll::new().die_type(DieType::Custom).count(10).add_custom_sides(&[-1]).target_roll(TargetRoll::GTE(-1))), Ok(10));
+            assert_eq!(roll(Roll::new().die_type(DieType::Custom).count(10).add_custom_sides(&[-1]).target_roll(TargetRoll::GT(-1))), Ok(0));
+            assert_eq!(roll(Roll::new().die_type(DieType::Custom).count(10).add_custom_sides(&[-2, -1]).target_roll(TargetRoll::LT(0))), Ok(10));
+        }
+
+        #[test]
+        fn explode_max() {
+            assert_eq!(roll(Roll::new().die_type(DieType::Custom).count(1).add_custom_sides(&[2]).explode(Explode::Exploding(None))), Ok(2 * Limits::default().dice as i64));
+            assert_eq!(roll(Roll::new().die_type(DieType::Custom).count(10).add_custom_sides(&[1, 2]).explode(Explode::Exploding(Some(TargetRoll::LT(0)))).target_roll(TargetRoll::GTE(1))), Ok(10));
+        }
+
+        #[test]
+        fn reroll() {
+            assert_eq!(roll(Roll::new().die_type(DieType::Custom).count(10).add_custom_sides(&[-1, 1]).reroll(Reroll::Always(TargetRoll::EQ(-1)))), Ok(10));
+        }
+
+        #[test]
+        fn overflow() {
+            assert_eq!(roll(Roll::new().die_type(DieType::Custom).count(2).add_custom_sides(&[i64::MAX])), Err(Error::Overflow));
+            assert_eq!(roll(Roll::new().die_type(DieType::Custom).count(1).add_custom_sides(&[i64::MAX]).explode(Explode::Compounding(None))), Err(Error::Overflow));
+            assert_eq!(roll(Roll::new().die_type(DieType::Custom).count(1).add_custom_sides(&[i64::MIN]).explode(Explode::Penetrating(None))), Err(Error::Overflow));
+            assert_eq!(roll(Roll::new().die_type(DieType::Custom).count(2).add_custom_sides(&[i64::MAX]).keep_highest(1)), Ok(i64::MAX));
         }
 
         #[test]
         fn max() {
-            let custom_sides: Vec<i64> = (1..(MAX_CUSTOM_SIDES * 2) as i64).collect();
-            let roll = Roll::new().die_type(DieType::Custom).count(u64::max_value()).add_custom_sides(&custom_sides[..]).roll_dice();
-            let max = (MAX_ROLLS as u64) * (MAX_CUSTOM_SIDES as u64);
-            assert!(MAX_ROLLS as i64 <= roll && roll <= max as i64);
+            let custom_sides: Vec<i64> = (1..(Limits::default().custom_sides * 2) as i64).collect();
+            let roll = roll(Roll::new().die_type(DieType::Custom).count(u64::MAX).add_custom_sides(&custom_sides[..])).unwrap();
+            let max = Limits::default().dice * Limits::default().custom_sides;
+            assert!(Limits::default().dice as i64 <= roll && roll <= max as i64);
         }
     }
 }
@@ -0,0 +1,53 @@
+// rouler - A container-based system for generating die rolls
+// Copyright (C) 2016 by John Berry
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use serde::de::{Deserialize, Deserializer, Error as _};
+use serde::ser::{Serialize, Serializer};
+
+use expr::Expr;
+use Roller;
+
+/// Reads a value from its syntax, reporting any syntax error as a deserialization error.
+fn from_syntax<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where D: Deserializer<'de>,
+          T: FromStr,
+          T::Err: ::std::fmt::Display
+{
+    let syntax = String::deserialize(deserializer)?;
+    syntax.parse().map_err(D::Error::custom)
+}
+
+/// Expressions and Rollers are stored as their syntax, so that they read naturally in a character sheet,
+/// eg. `{"damage": "2d6 + 3"}`.
+impl Serialize for Expr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Expr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        from_syntax(deserializer)
+    }
+}
+
+/// Only the syntax is stored, so neither the last roll nor a seeded Roller's position in its sequence of
+/// rolls survives being serialized.
+impl Serialize for Roller {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.expr().serialize(serializer)
+    }
+}
+
+/// Parses the stored syntax and makes a fresh roll, failing if the syntax is invalid or the roll fails.
+impl<'de> Deserialize<'de> for Roller {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        from_syntax(deserializer)
+    }
+}
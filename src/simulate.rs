@@ -0,0 +1,166 @@
+// rouler - A container-based system for generating die rolls
+// Copyright (C) 2016 by John Berry
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::BTreeMap;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use error::Error;
+use expr::Expr;
+
+/// The number of rolls in each batch of a simulation. Every batch draws from its own stream of random
+/// numbers, so a simulation gives the same results whether or not its batches are run in parallel.
+const BATCH_SIZE: u64 = 10_000;
+
+/// The totals of a number of simulated rolls, for rolls whose exact distribution is too expensive to
+/// work out.
+///
+/// A `Histogram` is made with [`simulate`](fn.simulate.html) or `Expr::simulate`. With the `parallel`
+/// feature enabled, the rolls are made in batches across threads.
+///
+/// # Examples
+///
+/// ```
+/// let pool = rouler::simulate("40d10!>=8", 10_000, 1234).unwrap();
+/// let (low, high) = pool.mean_interval(0.95);
+///
+/// assert_eq!(pool.samples(), 10_000);
+/// assert!(low < pool.mean() && pool.mean() < high);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    counts: BTreeMap<i64, u64>,
+    samples: u64,
+}
+
+impl Histogram {
+    /// Rolls `expr` `samples` times, from random numbers generated from `seed`.
+    pub(crate) fn simulate(expr: &Expr, samples: u64, seed: u64) -> Result<Self, Error> {
+        let batch = |index: u64| {
+            let mut rng = ChaCha20Rng::seed_from_u64(seed);
+            rng.set_stream(index);
+
+            let mut histogram = Histogram { counts: BTreeMap::new(), samples: 0 };
+            for _ in 0..BATCH_SIZE.min(samples - index * BATCH_SIZE) {
+                histogram.record(expr.eval_with(&mut rng)?);
+            }
+            Ok(histogram)
+        };
+
+        let batches = samples.div_ceil(BATCH_SIZE);
+        #[cfg(feature = "parallel")]
+        let batches = (0..batches).into_par_iter().map(batch).collect::<Result<Vec<Histogram>, Error>>()?;
+        #[cfg(not(feature = "parallel"))]
+        let batches = (0..batches).map(batch).collect::<Result<Vec<Histogram>, Error>>()?;
+
+        let mut histogram = Histogram { counts: BTreeMap::new(), samples: 0 };
+        for batch in batches {
+            for (value, count) in batch.counts {
+                *histogram.counts.entry(value).or_insert(0) += count;
+            }
+            histogram.samples += batch.samples;
+        }
+        Ok(histogram)
+    }
+
+    fn record(&mut self, total: i64) {
+        *self.counts.entry(total).or_insert(0) += 1;
+        self.samples += 1;
+    }
+
+    /// The number of rolls made.
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// The number of rolls that totalled `value`.
+    pub fn count(&self, value: i64) -> u64 {
+        self.counts.get(&value).cloned().unwrap_or(0)
+    }
+
+    /// Iterates over every total that was rolled in ascending order, along with the number of times it
+    /// was rolled.
+    pub fn iter(&self) -> impl Iterator<Item = (i64, u64)> + '_ {
+        self.counts.iter().map(|(&value, &count)| (value, count))
+    }
+
+    /// The fraction of rolls that totalled `value`.
+    pub fn probability(&self, value: i64) -> f64 {
+        self.count(value) as f64 / self.samples as f64
+    }
+
+    /// The lowest total rolled, or `None` if no rolls were made.
+    pub fn min(&self) -> Option<i64> {
+        self.counts.keys().next().cloned()
+    }
+
+    /// The highest total rolled, or `None` if no rolls were made.
+    pub fn max(&self) -> Option<i64> {
+        self.counts.keys().next_back().cloned()
+    }
+
+    /// The mean of the rolled totals, which is NaN if no rolls were made.
+    pub fn mean(&self) -> f64 {
+        self.iter().map(|(value, count)| value as f64 * count as f64).sum::<f64>() / self.samples as f64
+    }
+
+    /// The sample variance of the rolled totals.
+    pub fn variance(&self) -> f64 {
+        let mean = self.mean();
+        let squares = self.iter().map(|(value, count)| (value as f64 - mean).powi(2) * count as f64).sum::<f64>();
+        squares / (self.samples as f64 - 1.0)
+    }
+
+    /// The sample standard deviation of the rolled totals.
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    /// A confidence interval for the true mean of the roll, so that `mean_interval(0.95)` gives the 95%
+    /// confidence interval.
+    pub fn mean_interval(&self, confidence: f64) -> (f64, f64) {
+        let margin = z_score(confidence) * (self.variance() / self.samples as f64).sqrt();
+        (self.mean() - margin, self.mean() + margin)
+    }
+
+    /// A confidence interval for the true chance of the roll totalling `value`, from the Wilson score
+    /// interval, which behaves well even for rare totals.
+    pub fn probability_interval(&self, value: i64, confidence: f64) -> (f64, f64) {
+        let (z, n, p) = (z_score(confidence), self.samples as f64, self.probability(value));
+        let centre = (p + z * z / (2.0 * n)) / (1.0 + z * z / n);
+        let margin = z / (1.0 + z * z / n) * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt();
+        ((centre - margin).max(0.0), (centre + margin).min(1.0))
+    }
+}
+
+/// The number of standard deviations either side of the mean that hold `confidence` of a normal
+/// distribution, using Acklam's approximation of the inverse normal distribution.
+fn z_score(confidence: f64) -> f64 {
+    const A: [f64; 6] = [-3.969683028665376e1, 2.209460984245205e2, -2.759285104469687e2,
+                         1.38357751867269e2, -3.066479806614716e1, 2.506628277459239];
+    const B: [f64; 5] = [-5.447609879822406e1, 1.615858368580409e2, -1.556989798598866e2,
+                         6.680131188771972e1, -1.328068155288572e1];
+    const C: [f64; 6] = [-7.784894002430293e-3, -3.223964580411365e-1, -2.400758277161838,
+                         -2.549732539343734, 4.374664141464968, 2.938163982698783];
+    const D: [f64; 4] = [7.784695709041462e-3, 3.224671290700398e-1, 2.445134137142996,
+                         3.754408661907416];
+
+    let p = (1.0 + confidence.clamp(0.0, 1.0 - 1e-12)) / 2.0;
+    if p <= 0.97575 {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    } else {
+        let q = (-2.0 * (1.0 - p).ln()).sqrt();
+        -(((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    }
+}
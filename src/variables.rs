@@ -0,0 +1,65 @@
+// rouler - A container-based system for generating die rolls
+// Copyright (C) 2016 by John Berry
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::BuildHasher;
+
+/// The values of the variables referred to by an expression, such as `@str_mod` or `@{dex}`.
+///
+/// Variables are given to `Expr::bind`, `Expr::eval_in` or `Expr::roll_in`. Maps from `String` to `i64`
+/// implement `Variables`, and so can be used directly, while a character sheet might implement it to
+/// look up its own attributes.
+///
+/// # Examples
+///
+/// ```
+/// use rouler::{parse, Variables};
+///
+/// struct Sheet {
+///     strength: i64,
+/// }
+///
+/// impl Variables for Sheet {
+///     fn get(&self, name: &str) -> Option<i64> {
+///         match name {
+///             "str" => Some((self.strength - 10) / 2),
+///             _ => None,
+///         }
+///     }
+/// }
+///
+/// let damage = parse("1d1 + @str").unwrap();
+/// assert_eq!(damage.eval_in(&Sheet { strength: 16 }), Ok(4));
+/// ```
+pub trait Variables {
+    /// The value of the variable called `name`, or `None` if there is no such variable.
+    fn get(&self, name: &str) -> Option<i64>;
+}
+
+impl<S: BuildHasher> Variables for HashMap<String, i64, S> {
+    fn get(&self, name: &str) -> Option<i64> {
+        HashMap::get(self, name).cloned()
+    }
+}
+
+impl<S: BuildHasher> Variables for HashMap<&str, i64, S> {
+    fn get(&self, name: &str) -> Option<i64> {
+        HashMap::get(self, name).cloned()
+    }
+}
+
+impl Variables for BTreeMap<String, i64> {
+    fn get(&self, name: &str) -> Option<i64> {
+        BTreeMap::get(self, name).cloned()
+    }
+}
+
+impl Variables for BTreeMap<&str, i64> {
+    fn get(&self, name: &str) -> Option<i64> {
+        BTreeMap::get(self, name).cloned()
+    }
+}
@@ -5,13 +5,21 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+extern crate rand;
 extern crate rouler;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+
+use std::collections::HashMap;
+use std::thread;
+
+use rand::{rngs::StdRng, SeedableRng};
 
 use rouler::*;
 
 macro_rules! assert_range {
     ($begin:expr => $val:expr => $end:expr) => {
-        assert!(($begin <= $val) && ($val <= $end));
+        assert!(($begin <= $val) && ($val <= $end))
     };
 }
 
@@ -57,6 +65,62 @@ fn custom_dice_spaces_optional() {
     }
 }
 
+#[test]
+fn custom_dice_keep_drop() {
+    assert_eq!(Roller::new("1000d[1,2,3]kh2").total(), 6);
+    assert_eq!(Roller::new("1000d[1,2,3]kl2").total(), 2);
+    assert_eq!(Roller::new("1000d[-1,0,1]dh999").total(), -1);
+
+    for _ in 0..100 {
+        assert_range!(2 => roll_dice("4d[1,2,3]kh2") => 6);
+    }
+}
+
+#[test]
+fn custom_dice_target_roll() {
+    assert_eq!(roll_dice("10d[-1]>=-1"), 10);
+    assert_eq!(roll_dice("10d[-1]>-1"), 0);
+    assert_eq!(roll_dice("10d[-2, -1]<0"), 10);
+
+    for _ in 0..100 {
+        assert_range!(0 => roll_dice("6d[0,0,1,1,2]>=1") => 6);
+    }
+}
+
+#[test]
+fn custom_dice_modifiers() {
+    assert_eq!(roll_dice("1d[3]!"), 3000);
+    assert_eq!(roll_dice("10d[1, 2]r1"), 20);
+    assert_eq!(roll_dice("10d[1, 2]r1kh5"), 10);
+}
+
+#[test]
+fn fate_dice_within_range() {
+    for _ in 0..100 {
+        assert_range!(-4 => roll_dice("4dF") => 4);
+        assert_range!(-4 => roll_dice("4df") => 4);
+        assert_range!(-1 => roll_dice("dF") => 1);
+    }
+}
+
+#[test]
+fn fate_dice_modifiers() {
+    assert_eq!(roll_dice("1000dFkh1"), 1);
+    assert_eq!(roll_dice("1000dFkl1"), -1);
+    assert_eq!(roll_dice("10dF>=-1"), 10);
+    assert_range!(-3 => roll_dice("4dF + 1") => 5);
+}
+
+#[test]
+fn percentile_dice_within_range() {
+    for _ in 0..100 {
+        assert_range!(1 => roll_dice("1d%") => 100);
+        assert_range!(1 => roll_dice("d%") => 100);
+        assert_range!(3 => roll_dice("2d% + 1") => 201);
+    }
+    assert_eq!(roll_dice("1000d%kh1"), 100);
+}
+
 #[test]
 fn negative_dice_negates_roll_value() {
     assert_range!(-18 => Roller::new("-3d6").total() => -3);
@@ -132,6 +196,37 @@ fn keep_two() {
     assert_eq!(Roller::new("5d1KH2").total(), 2);
 }
 
+#[test]
+fn keep_lowest() {
+    assert_eq!(Roller::new("5d1kl2").total(), 2);
+    assert_eq!(Roller::new("5d1KL2").total(), 2);
+
+    for _ in 0..100 {
+        assert_range!(1 => Roller::new("2d20kl1").total() => 20);
+    }
+}
+
+#[test]
+fn drop_highest() {
+    assert_eq!(Roller::new("5d1dh2").total(), 3);
+    assert_eq!(Roller::new("5d1DH2").total(), 3);
+
+    for _ in 0..100 {
+        assert_range!(3 => Roller::new("4d6dh1").total() => 18);
+    }
+}
+
+#[test]
+fn take_keeps_the_right_dice() {
+    // With a thousand d20s, each face is all but certain to turn up
+    assert_eq!(Roller::new("1000d20kh1").total(), 20);
+    assert_eq!(Roller::new("1000d20k1").total(), 20);
+    assert_eq!(Roller::new("1000d20kl1").total(), 1);
+    assert_eq!(Roller::new("1000d20dh999").total(), 1);
+    assert_eq!(Roller::new("1000d20dl999").total(), 20);
+    assert_eq!(Roller::new("1000d20d999").total(), 20);
+}
+
 #[test]
 fn target_roll() {
     assert_eq!(Roller::new("5d1>=1").total(), 5);
@@ -149,6 +244,73 @@ fn target_roll() {
     assert_eq!(Roller::new("10d10kh8>=1").total(), 8);
 }
 
+#[test]
+fn exploding_dice() {
+    assert_eq!(Roller::new("1d1!").total(), 1000);
+    assert_range!(1 => Roller::new("1d6!>6").total() => 6);
+    assert_eq!(Roller::new("10d1!>1").total(), 10);
+    assert_eq!(Roller::new("1d1!1").total(), 1000);
+
+    for _ in 0..100 {
+        assert!(Roller::new("4d6!").total() >= 4);
+        assert!(Roller::new("3d10!>8").total() >= 3);
+    }
+}
+
+#[test]
+fn compounding_dice() {
+    assert_eq!(Roller::new("1d1!!").total(), 1000);
+    assert_eq!(Roller::new("2d1!!dl1").total(), 999);
+    assert_eq!(Roller::new("2d1!!<2dl1").total(), 999);
+}
+
+#[test]
+fn penetrating_dice() {
+    assert_eq!(Roller::new("1d1!p").total(), 1);
+    assert_eq!(Roller::new("1d1!P").total(), 1);
+    assert_eq!(Roller::new("1d1!p1==0").total(), 999);
+    assert_range!(1 => Roller::new("1d8!p>8").total() => 8);
+}
+
+#[test]
+fn exploding_dice_in_expressions() {
+    assert_eq!(Roller::new("1d1! + 1").total(), 1001);
+    assert_eq!(Roller::new("1d1!+1").total(), 1001);
+    assert_eq!(Roller::new("2 * 1d1!p").total(), 2);
+}
+
+#[test]
+fn reroll_dice() {
+    assert_eq!(Roller::new("10d2r1").total(), 20);
+    assert_eq!(Roller::new("10d2R1").total(), 20);
+    assert_eq!(Roller::new("10d2r<2").total(), 20);
+    assert_eq!(Roller::new("1d1r1").total(), 1);
+
+    for _ in 0..100 {
+        assert_range!(8 => Roller::new("4d6r1").total() => 24);
+        assert_range!(3 => Roller::new("1d10r<=2").total() => 10);
+        assert_range!(12 => Roller::new("4d6r1r2").total() => 24);
+    }
+}
+
+#[test]
+fn reroll_once_dice() {
+    assert_eq!(Roller::new("5d1ro1").total(), 5);
+    assert_eq!(Roller::new("5d1RO1").total(), 5);
+
+    for _ in 0..100 {
+        assert_range!(2 => Roller::new("2d20ro<3").total() => 40);
+    }
+}
+
+#[test]
+fn reroll_with_other_modifiers() {
+    assert_eq!(Roller::new("10d2r1dl5").total(), 10);
+    assert_eq!(Roller::new("10d2r1==2").total(), 10);
+    assert!(Roller::new("1d6r1!").total() >= 2);
+    assert!(Roller::new("1d6!r1").total() >= 2);
+}
+
 #[test]
 fn no_roll_specified() {
     assert_eq!(Roller::new("d1").total(), 1);
@@ -166,4 +328,857 @@ fn parens() {
     assert_eq!(Roller::new("(1)").total(), 1);
     assert_eq!(Roller::new("2 * (1+1)").total(), 4);
     assert_eq!(Roller::new("2 * (100d1)").total(), 200);
-}
\ No newline at end of file
+}
+#[test]
+fn syntax_errors() {
+    assert_eq!(roll_dice_or_fail("food4"), Err(Error::Syntax { span: 0..0, expected: vec!["an expression".to_string()] }));
+    assert_eq!(roller_or_fail("/ro 1 + 1").unwrap_err(), Error::Syntax { span: 2..2, expected: vec!["an expression".to_string()] });
+
+    match roll_dice_or_fail("(1") {
+        Err(Error::Syntax { span, expected }) => {
+            assert_eq!(span, 2..2);
+            assert!(expected.contains(&"`+`".to_string()));
+        },
+        _ => panic!("expected a syntax error"),
+    }
+}
+
+#[test]
+fn errors_are_displayable() {
+    let err: Box<dyn std::error::Error> = Box::new(roll_dice_or_fail("").unwrap_err());
+    assert_eq!(err.to_string(), "invalid roll at position 0, expected an expression");
+    assert_eq!(Error::DivisionByZero.to_string(), "division by zero");
+    assert_eq!(Error::LimitExceeded { limit: Limit::Dice, max: 1000 }.to_string(), "too many dice (the limit is 1000)");
+}
+
+#[test]
+fn division_by_zero_is_an_error() {
+    assert_eq!(roll_dice_or_fail("1/0"), Err(Error::DivisionByZero));
+    assert_eq!(roll_dice_or_fail("1d6 / (1d1 - 1)"), Err(Error::DivisionByZero));
+    assert!(roller_or_fail("10 / 0").is_err());
+}
+
+#[test]
+#[should_panic(expected = "Failed to roll: division by zero")]
+fn division_by_zero_panics() {
+    roll_dice("1/0");
+}
+
+#[test]
+fn overflow_is_an_error() {
+    assert_eq!(roll_dice_or_fail("9223372036854775807 + 1"), Err(Error::Overflow));
+    assert_eq!(roll_dice_or_fail("-9223372036854775807 - 2"), Err(Error::Overflow));
+    assert_eq!(roll_dice_or_fail("4611686018427387904 * 2"), Err(Error::Overflow));
+    assert_eq!(roll_dice_or_fail("-9223372036854775808 / -1"), Err(Error::Overflow));
+    assert_eq!(roll_dice_or_fail("2d[9223372036854775807]"), Err(Error::Overflow));
+
+    assert_eq!(roll_dice_or_fail("9223372036854775807"), Ok(9223372036854775807));
+    assert_eq!(roll_dice_or_fail("-9223372036854775808"), Ok(-9223372036854775808));
+}
+
+#[test]
+fn out_of_range_literals_are_errors() {
+    assert_eq!(roll_dice_or_fail("99999999999999999999"), Err(Error::Overflow));
+    assert_eq!(roll_dice_or_fail("99999999999999999999d6"), Err(Error::Overflow));
+    assert_eq!(roll_dice_or_fail("1d99999999999999999999"), Err(Error::Overflow));
+    assert_eq!(roll_dice_or_fail("1d[1, 99999999999999999999]"), Err(Error::Overflow));
+    assert_eq!(roll_dice_or_fail("4d6kh99999999999999999999"), Err(Error::Overflow));
+    assert_eq!(roll_dice_or_fail("4d6>=99999999999999999999"), Err(Error::Overflow));
+}
+
+#[test]
+fn parsed_expressions_are_reusable() {
+    let expr = parse("2d1 + 3").unwrap();
+    for _ in 0..100 {
+        assert_eq!(expr.eval(), Ok(5));
+    }
+
+    let expr = parse("3d6").unwrap();
+    for _ in 0..100 {
+        assert_range!(3 => expr.eval().unwrap() => 18);
+    }
+}
+
+#[test]
+fn parse_errors() {
+    assert_eq!(parse("food4").unwrap_err(), Error::Syntax { span: 0..0, expected: vec!["an expression".to_string()] });
+    assert_eq!(parse("99999999999999999999").unwrap_err(), Error::Overflow);
+
+    // Arithmetic errors are only found by rolling
+    let expr = parse("1/0").unwrap();
+    assert_eq!(expr.eval(), Err(Error::DivisionByZero));
+}
+
+#[test]
+fn rollers_keep_their_expression() {
+    let mut roller = Roller::new("1d1 + 1");
+    assert_eq!(roller.expr().eval(), Ok(2));
+    assert_eq!(roller.iter().take(1000).sum::<i64>(), 2000);
+}
+
+fn dice_result(result: &RollResult) -> &DiceResult {
+    match *result {
+        RollResult::Dice(ref dice) => dice,
+        _ => panic!("expected a dice result"),
+    }
+}
+
+#[test]
+fn roll_result_tree() {
+    let roller = Roller::new("2d1 * 3");
+    let one = Die { value: 1, dropped: false, success: false };
+
+    assert_eq!(*roller.result(), RollResult::Operation {
+        op: Op::Multiply,
+        lhs: Box::new(RollResult::Dice(DiceResult {
+            die_type: DieType::Normal,
+            dice: vec![one, one],
+            counts_successes: false,
+            total: 2,
+            warnings: Vec::new(),
+        })),
+        rhs: Box::new(RollResult::Number(3)),
+        total: 6,
+    });
+}
+
+#[test]
+fn roll_result_individual_dice() {
+    let mut roller = Roller::new("4d6");
+    for _ in 0..100 {
+        let total = roller.reroll();
+        let dice = dice_result(roller.result());
+
+        assert_eq!(dice.dice.len(), 4);
+        assert!(dice.dice.iter().all(|die| 1 <= die.value && die.value <= 6));
+        assert_eq!(dice.dice.iter().map(|die| die.value).sum::<i64>(), total);
+    }
+}
+
+#[test]
+fn roll_result_dropped_dice() {
+    let roller = Roller::new("1000d20kh1");
+    let dice = dice_result(roller.result());
+    let kept = dice.kept().collect::<Vec<_>>();
+
+    assert_eq!(dice.dice.len(), 1000);
+    assert_eq!(kept.len(), 1);
+    assert_eq!(kept[0].value, 20);
+}
+
+#[test]
+fn roll_result_successes() {
+    let roller = Roller::new("5d1dl2>=1");
+    let dice = dice_result(roller.result());
+
+    assert!(dice.counts_successes);
+    assert_eq!(dice.total, 3);
+    assert_eq!(dice.dice.iter().filter(|die| die.success).count(), 3);
+    assert!(dice.dice.iter().all(|die| die.success != die.dropped));
+
+    let roller = Roller::new("5d1>1");
+    assert!(dice_result(roller.result()).dice.iter().all(|die| !die.success));
+}
+
+#[test]
+fn roll_result_exploded_dice() {
+    let roller = Roller::new("1d1!");
+    assert_eq!(dice_result(roller.result()).dice.len(), 1000);
+
+    let roller = Roller::new("4dF");
+    assert_eq!(dice_result(roller.result()).die_type, DieType::Fate);
+}
+
+#[test]
+fn render_styles() {
+    let roller = Roller::new("3d1dl1 + 4");
+    assert_eq!(roller.result().render(Style::Plain), "(~1~+1+1)+4 = 6");
+    assert_eq!(roller.result().render(Style::Markdown), "(~~1~~+1+1)+4 = 6");
+    assert_eq!(roller.result().render(Style::Ansi), "(\x1b[2;9m1\x1b[0m+1+1)+4 = 6");
+
+    let roller = Roller::new("2d1>=1 * 2");
+    assert_eq!(roller.result().render(Style::Plain), "(*1*+*1*)*2 = 4");
+    assert_eq!(roller.result().render(Style::Markdown), "(**1**+**1**)\\*2 = 4");
+    assert_eq!(roller.result().render(Style::Ansi), "(\x1b[1;32m1\x1b[0m+\x1b[1;32m1\x1b[0m)*2 = 4");
+}
+
+#[test]
+fn render_precedence() {
+    assert_eq!(Roller::new("(1d1 + 2) * 3").result().to_string(), "((1)+2)*3 = 9");
+    assert_eq!(Roller::new("1d1 + 2 * 3").result().to_string(), "(1)+2*3 = 7");
+    assert_eq!(Roller::new("10 - (1d1 - 1)").result().to_string(), "10-((1)-1) = 10");
+    assert_eq!(Roller::new("10 - 1d1 - 1").result().to_string(), "10-(1)-1 = 8");
+}
+
+#[test]
+fn render_fate_dice() {
+    assert_eq!(Roller::new("4d[0]").result().to_string(), "(0+0+0+0) = 0");
+
+    let roller = Roller::new("1000dF");
+    let rendered = roller.result().to_string();
+    assert!(rendered.starts_with("(["));
+    assert!(rendered.contains("[+]") && rendered.contains("[-]") && rendered.contains("[ ]"));
+}
+
+#[test]
+fn roller_alternate_display() {
+    let roller = Roller::new("2d1 + 5");
+    assert_eq!(format!("{}", roller), "[2d1 + 5: 7]");
+    assert_eq!(format!("{:#}", roller), "[2d1 + 5: (1+1)+5 = 7]");
+}
+
+#[test]
+fn seeded_rollers_repeat() {
+    let mut first = Roller::with_seed("10d20!r1 + 3d[1,2,3]kh2", 42);
+    let mut second = Roller::with_seed("10d20!r1 + 3d[1,2,3]kh2", 42);
+    assert_eq!(first.total(), second.total());
+    assert_eq!(first.iter().take(50).collect::<Vec<i64>>(), second.iter().take(50).collect::<Vec<i64>>());
+
+    let mut other = Roller::with_seed("10d20!r1 + 3d[1,2,3]kh2", 43);
+    let rolls = Roller::with_seed("10d20!r1 + 3d[1,2,3]kh2", 42).iter().take(50).collect::<Vec<i64>>();
+    assert_ne!(rolls, other.iter().take(50).collect::<Vec<i64>>());
+}
+
+#[test]
+fn roll_with_rng() {
+    let first = roll_dice_with("100d6 + 4dF", &mut StdRng::seed_from_u64(7));
+    assert_eq!(roll_dice_with("100d6 + 4dF", &mut StdRng::seed_from_u64(7)), first);
+
+    let mut roller = Roller::new("100d6");
+    let first = roller.reroll_with(&mut StdRng::seed_from_u64(7));
+    assert_eq!(roller.reroll_with(&mut StdRng::seed_from_u64(7)), first);
+
+    let expr = parse("3d6 * 2").unwrap();
+    let result = expr.roll_with(&mut StdRng::seed_from_u64(9)).unwrap();
+    assert_eq!(expr.eval_with(&mut StdRng::seed_from_u64(9)), Ok(result.total()));
+}
+
+fn assert_probability(distribution: &Distribution, value: i64, expected: f64) {
+    let probability = distribution.probability(value);
+    assert!((probability - expected).abs() < 1e-9, "P({}) = {}, expected {}", value, probability, expected);
+}
+
+#[test]
+fn distribution_sums() {
+    let dist = distribution("3d6 + 2").unwrap();
+    assert_eq!(dist.iter().next(), Some((5, dist.probability(5))));
+    assert_eq!(dist.iter().last().map(|(value, _)| value), Some(20));
+    assert_probability(&dist, 5, 1.0 / 216.0);
+    assert_probability(&dist, 12, 27.0 / 216.0);
+    assert_probability(&dist, 14, 25.0 / 216.0);
+    assert!((dist.iter().map(|(_, p)| p).sum::<f64>() - 1.0).abs() < 1e-12);
+
+    assert_probability(&distribution("4dF").unwrap(), 0, 19.0 / 81.0);
+    assert_probability(&distribution("d%").unwrap(), 100, 0.01);
+    assert_probability(&distribution("2d[1, 1, 2]").unwrap(), 2, 4.0 / 9.0);
+    assert_probability(&distribution("0d6 + 1d0").unwrap(), 0, 1.0);
+}
+
+#[test]
+fn distribution_keep_and_drop() {
+    let dist = distribution("4d6kh3").unwrap();
+    assert_probability(&dist, 18, 21.0 / 1296.0);
+    assert_probability(&dist, 3, 1.0 / 1296.0);
+    assert_eq!(distribution("4d6dl1"), Ok(dist));
+
+    let advantage = distribution("2d20kh1").unwrap();
+    let disadvantage = distribution("2d20kl1").unwrap();
+    for value in 1..21 {
+        assert_probability(&advantage, value, (2 * value - 1) as f64 / 400.0);
+        assert_probability(&disadvantage, 21 - value, (2 * value - 1) as f64 / 400.0);
+    }
+    assert_eq!(distribution("2d20dh1"), Ok(disadvantage));
+    assert_probability(&distribution("3d6kh5").unwrap(), 18, 1.0 / 216.0);
+}
+
+#[test]
+fn distribution_successes() {
+    let dist = distribution("5d10>=8").unwrap();
+    assert_probability(&dist, 0, 0.7f64.powi(5));
+    assert_probability(&dist, 5, 0.3f64.powi(5));
+    assert_probability(&dist, 2, 10.0 * 0.3f64.powi(2) * 0.7f64.powi(3));
+
+    let kept = distribution("3d6kh1>=6").unwrap();
+    assert_probability(&kept, 1, 1.0 - (5.0f64 / 6.0).powi(3));
+}
+
+#[test]
+fn distribution_arithmetic() {
+    assert_probability(&distribution("1d6 * 10").unwrap(), 60, 1.0 / 6.0);
+    assert_probability(&distribution("1d6 * 10").unwrap(), 61, 0.0);
+    let halved = distribution("1d6 / 2").unwrap();
+    assert_probability(&halved, 0, 1.0 / 6.0);
+    assert_probability(&halved, 1, 2.0 / 6.0);
+    assert_probability(&halved, 3, 1.0 / 6.0);
+    assert_probability(&distribution("1d6 - 1d6").unwrap(), 0, 6.0 / 36.0);
+    assert_probability(&distribution("10 - (1d4 + 1)").unwrap(), 5, 0.25);
+}
+
+#[test]
+fn distribution_rerolls_and_explosions() {
+    assert_probability(&distribution("1d6r1").unwrap(), 2, 0.2);
+    assert_probability(&distribution("1d6r1").unwrap(), 1, 0.0);
+    assert_probability(&distribution("1d6ro1").unwrap(), 1, 1.0 / 36.0);
+    assert_probability(&distribution("1d6ro1").unwrap(), 2, 7.0 / 36.0);
+
+    let exploding = distribution("1d6!").unwrap();
+    assert_probability(&exploding, 6, 0.0);
+    assert_probability(&exploding, 7, 1.0 / 36.0);
+    assert_probability(&exploding, 13, 1.0 / 216.0);
+    assert_eq!(distribution("1d6!!"), Ok(exploding));
+    assert_probability(&distribution("1d6!p").unwrap(), 6, 1.0 / 36.0);
+    assert_probability(&distribution("1d6!>=5>=5").unwrap(), 0, 4.0 / 6.0);
+    assert_probability(&distribution("1d1!").unwrap(), 1000, 1.0);
+    assert_probability(&distribution("2d6!!kh1").unwrap(), 5, 9.0 / 36.0);
+}
+
+#[test]
+fn distribution_errors() {
+    assert_eq!(distribution("2d6!kh1"), Err(Error::Unsupported));
+    assert_eq!(distribution("1d6 / (1d2 - 1)"), Err(Error::DivisionByZero));
+    assert_eq!(distribution("1d1000000"), Err(Error::LimitExceeded { limit: Limit::Outcomes, max: 100_000 }));
+    assert_eq!(distribution("1000d1000"), Err(Error::LimitExceeded { limit: Limit::Outcomes, max: 100_000 }));
+
+    // Rolls too slow to work out stop early rather than tying up the caller
+    let too_slow = Error::LimitExceeded { limit: Limit::Work, max: 100_000_000 };
+    assert_eq!(distribution("1000d6kh500"), Err(too_slow.clone()));
+    assert_eq!(parse("1000d6kh500").unwrap().mean(), Err(too_slow.clone()));
+    assert_eq!(distribution("1000d100"), Err(too_slow));
+
+    // while large rolls within the limit are still exact
+    assert!((distribution("1000d6").unwrap().mean() - 3500.0).abs() < 1e-6);
+    let highest = distribution("100d6kh50").unwrap().mean();
+    let lowest = distribution("100d6kl50").unwrap().mean();
+    assert!((highest + lowest - 350.0).abs() < 1e-6);
+    assert!(distribution("food4").is_err());
+}
+
+#[test]
+fn summary_statistics() {
+    let damage = parse("2d6 + 3").unwrap();
+    assert!((damage.mean().unwrap() - 10.0).abs() < 1e-9);
+    assert!((damage.std_dev().unwrap() - (35.0f64 / 6.0).sqrt()).abs() < 1e-9);
+    assert_eq!((damage.min(), damage.max()), (Ok(5), Ok(15)));
+    assert_eq!(damage.percentile(50.0), Ok(10));
+
+    // Kept dice fall back to the distribution
+    let stat = parse("4d6kh3").unwrap();
+    assert!((stat.mean().unwrap() - 15869.0 / 1296.0).abs() < 1e-9);
+    assert_eq!((stat.min(), stat.max()), (Ok(3), Ok(18)));
+    assert_eq!(stat.mean().unwrap(), distribution("4d6kh3").unwrap().mean());
+
+    let product = parse("1d4 * 1d6").unwrap();
+    let exact = distribution("1d4 * 1d6").unwrap();
+    assert!((product.mean().unwrap() - exact.mean()).abs() < 1e-9);
+    assert!((product.std_dev().unwrap() - exact.std_dev()).abs() < 1e-9);
+    assert_eq!((product.min(), product.max()), (Ok(1), Ok(24)));
+    assert_eq!(parse("(1d6 - 4) * 1d2").unwrap().min(), Ok(-6));
+
+    let halved = parse("1d6 / 2").unwrap();
+    assert!((halved.mean().unwrap() - 1.5).abs() < 1e-9);
+    assert_eq!((halved.min(), halved.max()), (Ok(0), Ok(3)));
+
+    assert_eq!(parse("5d10>=8").unwrap().max(), Ok(5));
+    assert!((parse("5d10>=8").unwrap().mean().unwrap() - 1.5).abs() < 1e-9);
+    assert_eq!(parse("1000d6").unwrap().max(), Ok(6000));
+
+    // Explosions are followed for as long as the limits allow, rather than as far as the distribution goes
+    let bounds = |r| parse(r).map(|expr| (expr.min().unwrap(), expr.max().unwrap())).unwrap();
+    assert_eq!(bounds("1d6!"), (1, 6000));
+    assert_eq!(bounds("1d6!!"), (1, 6000));
+    assert_eq!(bounds("1d6!p"), (1, 6 + 999 * 5));
+    assert_eq!(bounds("2d6!"), (2, 6000));
+    assert_eq!(bounds("1d6!1"), (2, 999 + 6));
+    assert_eq!(bounds("1d1!"), (1000, 1000));
+    assert_eq!(bounds("4d6!!kh1"), (1, 997 * 6));
+    assert_eq!(bounds("3d6!>=5"), (3, 6000));
+    assert_eq!(bounds("3d6!6>=5"), (0, 1000));
+    assert_eq!(roll_dice("1d1!"), 1000);
+    assert_eq!(parse("1d6 / (1d2 - 1)").unwrap().max(), Err(Error::DivisionByZero));
+
+    // A denominator either side of zero which can't be zero is divided by each of its totals
+    let either_side = parse("10 / 1d[-1,1]").unwrap();
+    assert_eq!((either_side.min(), either_side.max()), (Ok(-10), Ok(10)));
+    assert_eq!(either_side.mean(), Ok(0.0));
+    assert_eq!(bounds("1d12 / 1d[-4,-2,3,5]"), (-6, 4));
+    assert_eq!(parse("9223372036854775807 + 1").unwrap().mean(), Err(Error::Overflow));
+    assert_eq!(parse("-(-9223372036854775807 - 1d2)").unwrap().std_dev(), Err(Error::Overflow));
+    assert_eq!(parse("1000d[9223372036854775807]").unwrap().mean(), Err(Error::Overflow));
+}
+
+#[test]
+fn chance_of_meeting_a_target() {
+    let hit = |comparison, threshold| chance("1d20 + 7", comparison, threshold).unwrap();
+    assert!((hit(Comparison::GTE, 16) - 0.6).abs() < 1e-12);
+    assert!((hit(Comparison::GT, 16) - 0.55).abs() < 1e-12);
+    assert!((hit(Comparison::LT, 16) - 0.4).abs() < 1e-12);
+    assert!((hit(Comparison::LTE, 16) - 0.45).abs() < 1e-12);
+    assert!((hit(Comparison::EQ, 16) - 0.05).abs() < 1e-12);
+    assert_eq!(hit(Comparison::GTE, 28), 0.0);
+    assert!((hit(Comparison::GTE, 8) - 1.0).abs() < 1e-12);
+
+    let advantage = parse("2d20kh1 + 5").unwrap();
+    assert!((advantage.chance(Comparison::GTE, 20).unwrap() - 0.51).abs() < 1e-12);
+    assert_eq!(chance("1d6 / 0", Comparison::GT, 0), Err(Error::DivisionByZero));
+}
+
+#[test]
+fn opposed_rolls() {
+    let odds = opposed("1d20 + 5", "1d20 + 2").unwrap();
+    let (mut win, mut tie, mut lose) = (0, 0, 0);
+    for att in 1..21 {
+        for def in 1..21 {
+            match (att + 5).cmp(&(def + 2)) {
+                std::cmp::Ordering::Greater => win += 1,
+                std::cmp::Ordering::Equal => tie += 1,
+                std::cmp::Ordering::Less => lose += 1,
+            }
+        }
+    }
+    assert!((odds.win - win as f64 / 400.0).abs() < 1e-12);
+    assert!((odds.tie - tie as f64 / 400.0).abs() < 1e-12);
+    assert!((odds.lose - lose as f64 / 400.0).abs() < 1e-12);
+
+    let reversed = parse("1d20 + 2").unwrap().against(&parse("1d20 + 5").unwrap()).unwrap();
+    assert!((reversed.win - odds.lose).abs() < 1e-12);
+
+    let certain = opposed("1d6 + 10", "1d6").unwrap();
+    assert!((certain.win - 1.0).abs() < 1e-12);
+    assert_eq!((certain.tie, certain.lose), (0.0, 0.0));
+    let hopeless = opposed("1d6", "1d6 + 10").unwrap();
+    assert_eq!((hopeless.win, hopeless.tie), (0.0, 0.0));
+    assert!((hopeless.lose - 1.0).abs() < 1e-12);
+    assert!(opposed("1d6", "1d6 / 0").is_err());
+}
+
+#[test]
+fn simulated_rolls() {
+    let histogram = simulate("3d6", 50_000, 7).unwrap();
+    assert_eq!(histogram.samples(), 50_000);
+    assert_eq!(histogram.iter().map(|(_, count)| count).sum::<u64>(), 50_000);
+    assert_eq!((histogram.min(), histogram.max()), (Some(3), Some(18)));
+    assert_eq!(histogram, simulate("3d6", 50_000, 7).unwrap());
+    assert!(histogram != simulate("3d6", 50_000, 8).unwrap());
+
+    let (low, high) = histogram.mean_interval(0.99);
+    assert!(low < 10.5 && 10.5 < high);
+    assert!((histogram.variance() - 8.75).abs() < 0.5);
+    let (low, high) = histogram.probability_interval(10, 0.99);
+    assert!(low < 27.0 / 216.0 && 27.0 / 216.0 < high);
+    assert_eq!(histogram.probability(2), 0.0);
+
+    // Larger intervals for greater confidence
+    let (narrow, wide) = (histogram.mean_interval(0.5), histogram.mean_interval(0.999));
+    assert!(wide.0 < narrow.0 && narrow.1 < wide.1);
+
+    let empty = simulate("3d6", 0, 7).unwrap();
+    assert_eq!((empty.samples(), empty.min()), (0, None));
+    assert_eq!(simulate("1d6 / (1d2 - 1)", 1000, 7), Err(Error::DivisionByZero));
+    assert_eq!(simulate("2d1 + 1", 12_345, 1).unwrap().count(3), 12_345);
+}
+
+#[test]
+fn export_distributions() {
+    let dist = distribution("2d6").unwrap();
+    let chart = dist.chart(12);
+    let lines = chart.lines().collect::<Vec<&str>>();
+    assert_eq!(lines.len(), 11);
+    assert_eq!(lines[0], " 2   2.78% ##");
+    assert_eq!(lines[5], " 7  16.67% ############");
+    assert_eq!(lines[10], "12   2.78% ##");
+
+    let csv = dist.csv();
+    let rows = csv.lines().collect::<Vec<&str>>();
+    assert_eq!(rows[0], "value,probability,cumulative");
+    assert_eq!(rows.len(), 12);
+    assert!(rows[1].starts_with("2,0.0277"));
+    let last = rows[11].split(',').collect::<Vec<&str>>();
+    assert_eq!(last[0], "12");
+    assert!((last[2].parse::<f64>().unwrap() - 1.0).abs() < 1e-12);
+
+    let json = dist.json();
+    assert!(json.starts_with(r#"[{"value":2,"probability":0.0277"#));
+    assert_eq!(json.matches(r#""value""#).count(), 11);
+    assert!(json.ends_with("}]"));
+
+    let fixed = distribution("1d1 + 4").unwrap();
+    assert_eq!(fixed.chart(5), "5 100.00% #####\n");
+    assert_eq!(fixed.csv(), "value,probability,cumulative\n5,1,1\n");
+}
+
+#[test]
+fn export_histograms() {
+    let histogram = simulate("2d1", 100, 1).unwrap();
+    assert_eq!(histogram.chart(4), "2 100.00% ####\n");
+    assert_eq!(histogram.csv(), "value,probability,cumulative\n2,1,1\n");
+    assert_eq!(histogram.json(), r#"[{"value":2,"probability":1,"cumulative":1}]"#);
+
+    let empty = simulate("2d1", 0, 1).unwrap();
+    assert_eq!(empty.chart(10), "");
+    assert_eq!(empty.csv(), "value,probability,cumulative\n");
+    assert_eq!(empty.json(), "[]");
+}
+
+#[test]
+fn recorded_rolls() {
+    let expr = parse("2d6 + 1d4").unwrap();
+    let (result, log) = expr.roll_recorded(&mut StdRng::seed_from_u64(7)).unwrap();
+    assert_eq!(result, expr.roll_with(&mut StdRng::seed_from_u64(7)).unwrap());
+
+    assert_eq!(log.expression, "2d6 + 1d4");
+    let positions = log.draws.iter()
+        .map(|draw| (draw.term.as_str(), draw.term_index, draw.index))
+        .collect::<Vec<(&str, usize, usize)>>();
+    assert_eq!(positions, vec![("2d6", 0, 0), ("2d6", 0, 1), ("1d4", 1, 0)]);
+    assert_eq!(log.draws.iter().map(|draw| draw.face).sum::<i64>(), result.total());
+
+    let (_, explosions) = parse("10d6!").unwrap().roll_recorded(&mut StdRng::seed_from_u64(3)).unwrap();
+    assert!(explosions.draws.len() >= 10);
+}
+
+#[test]
+fn replayed_rolls() {
+    let expr = parse("4d6kh3 + 1d20r1").unwrap();
+    let (result, log) = expr.roll_recorded(&mut rand::thread_rng()).unwrap();
+    assert_eq!(expr.replay(&log), Ok(result.clone()));
+    assert_eq!(parse("4d6kh3+1d20r1").unwrap().replay(&log), Ok(result.clone()));
+    assert_eq!(parse("4d6kh2 + 1d20r1").unwrap().replay(&log), Err(Error::ReplayMismatch { draw: 0 }));
+    assert_eq!(parse("4d6kh3 + 1d12").unwrap().replay(&log), Err(Error::ReplayMismatch { draw: 4 }));
+
+    let mut tampered = log.clone();
+    tampered.draws[1].face = 7;
+    assert_eq!(expr.replay(&tampered), Err(Error::ReplayMismatch { draw: 1 }));
+
+    let mut extra = log.clone();
+    extra.draws.push(log.draws[0].clone());
+    assert_eq!(expr.replay(&extra), Err(Error::ReplayMismatch { draw: log.draws.len() }));
+
+    let mut short = log.clone();
+    short.draws.pop();
+    assert_eq!(expr.replay(&short), Err(Error::ReplayMismatch { draw: log.draws.len() - 1 }));
+}
+
+#[test]
+fn fair_rolls() {
+    let seed = Seed::from_bytes([0; 32]);
+    assert_eq!(seed.commitment(), "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925");
+    assert_eq!(Seed::from_hex(&seed.to_hex()), Ok(seed.clone()));
+    assert_eq!(Seed::from_hex("00"), Err(Error::InvalidSeed));
+    assert_eq!(Seed::from_hex(&"zz".repeat(32)), Err(Error::InvalidSeed));
+    assert_eq!(Seed::from_hex(&"+f".repeat(32)), Err(Error::InvalidSeed));
+    assert_eq!(Seed::from_hex(&"AB".repeat(32)), Ok(Seed::from_bytes([0xab; 32])));
+
+    let seed = Seed::generate();
+    let commitment = seed.commitment();
+    let expr = parse("20d20").unwrap();
+    let result = expr.roll_fair(&seed, "nonce").unwrap();
+    assert_eq!(expr.roll_fair(&seed, "nonce"), Ok(result.clone()));
+    assert_ne!(expr.roll_fair(&seed, "another nonce"), Ok(result.clone()));
+
+    assert_eq!(verify("20d20", &commitment, &seed, "nonce", &result), Ok(true));
+    assert_eq!(verify("20d20", &commitment.to_uppercase(), &seed, "nonce", &result), Ok(true));
+    assert_eq!(verify("20d20", &commitment, &Seed::generate(), "nonce", &result), Ok(false));
+    assert_eq!(verify("20d20", &commitment, &seed, "another nonce", &result), Ok(false));
+    assert_eq!(verify("20d20", &commitment, &seed, "nonce", &expr.roll().unwrap()), Ok(false));
+    assert!(verify("food4", &commitment, &seed, "nonce", &result).is_err());
+}
+
+#[test]
+fn default_limits_clamp() {
+    let expr = parse("5000d1 + 1d1").unwrap();
+    assert_eq!(expr.eval(), Ok(1001));
+    assert_eq!(expr.warnings(), &[Warning::Clamped { limit: Limit::Dice, max: 1000 }]);
+    assert!(parse("3d6").unwrap().warnings().is_empty());
+
+    assert_eq!(parse("1d5000000000").unwrap().warnings(), &[Warning::Clamped { limit: Limit::Sides, max: u32::MAX as u64 }]);
+    let faces = (1..=1500).map(|face| face.to_string()).collect::<Vec<String>>().join(",");
+    assert_eq!(parse(&format!("1d[{}]", faces)).unwrap().max(), Ok(1000));
+}
+
+#[test]
+fn configured_limits() {
+    let strict = Limits {
+        dice: 20,
+        total_dice: 30,
+        sides: 100,
+        custom_sides: 4,
+        depth: 2,
+        explosions: 5,
+        policy: Policy::Error,
+    };
+    let parse = |r| parse_with_limits(r, &strict);
+    let exceeded = |limit, max| Err(Error::LimitExceeded { limit, max });
+
+    assert!(parse("20d100 + 10d6").is_ok());
+    assert_eq!(parse("21d6").err(), exceeded(Limit::Dice, 20).err());
+    assert_eq!(parse("20d6 + 10d6 + 1d6").err(), exceeded(Limit::TotalDice, 30).err());
+    assert_eq!(parse("1d101").err(), exceeded(Limit::Sides, 100).err());
+    assert_eq!(parse("1d[1,2,3,4,5]").err(), exceeded(Limit::CustomSides, 4).err());
+    assert!(parse("((1d6))").is_ok());
+    assert_eq!(parse("(((1d6)))").err(), exceeded(Limit::Depth, 2).err());
+
+    assert_eq!(parse("1d1!").unwrap().eval(), exceeded(Limit::Explosions, 5));
+    assert_eq!(parse("15d1r1").unwrap().eval(), exceeded(Limit::Dice, 20));
+    assert_eq!(parse("15d6 + 10d1r1").unwrap().eval(), exceeded(Limit::TotalDice, 30));
+    assert_eq!(parse("1d1!5").unwrap().eval(), Ok(1));
+
+    let lenient = Limits { policy: Policy::Clamp, ..strict };
+    let parse = |r| parse_with_limits(r, &lenient).unwrap();
+    let expr = parse("25d1 + 15d1");
+    assert_eq!(expr.eval(), Ok(30));
+    assert_eq!(expr.warnings(), &[Warning::Clamped { limit: Limit::Dice, max: 20 }, Warning::Clamped { limit: Limit::TotalDice, max: 30 }]);
+    assert_eq!(expr.warnings()[1].to_string(), "too many dice in total, so only 30 were used");
+    assert_eq!(parse("1d1!").eval(), Ok(6));
+    let exploded = parse("1d1!").roll().unwrap();
+    assert_eq!((exploded.total(), exploded.warnings()), (6, vec![Warning::Clamped { limit: Limit::Explosions, max: 5 }]));
+    assert_eq!(parse("15d1r1").roll().unwrap().warnings(), vec![Warning::Clamped { limit: Limit::Dice, max: 20 }]);
+    assert_eq!(parse("15d6 + 10d1r1").roll().unwrap().warnings(), vec![Warning::Clamped { limit: Limit::TotalDice, max: 30 }]);
+    assert!(parse("1d1!5").roll().unwrap().warnings().is_empty());
+    assert_eq!(parse("1d1!").distribution().unwrap().max(), 6);
+    assert_eq!((parse("1d1!").min(), parse("1d1!").max()), (Ok(6), Ok(6)));
+    assert_eq!(parse("15d6 + 10d1!").eval().map(|total| total <= 15 * 6 + 15), Ok(true));
+    assert!(parse_with_limits("(((1d6)))", &lenient).is_err());
+
+    let few_sides = Limits { sides: 10, policy: Policy::Error, ..Limits::default() };
+    assert_eq!(parse_with_limits("1d%", &few_sides).err(), exceeded(Limit::Sides, 10).err());
+    assert!(parse_with_limits("4dF", &few_sides).is_ok());
+    let few_sides = Limits { sides: 2, ..Limits::default() };
+    let percentile = parse_with_limits("2d%", &few_sides).unwrap();
+    assert_eq!((percentile.max(), percentile.warnings()), (Ok(4), &[Warning::Clamped { limit: Limit::Sides, max: 2 }][..]));
+    let fate = parse_with_limits("4dF", &few_sides).unwrap();
+    assert_eq!((fate.min(), fate.max()), (Ok(-4), Ok(0)));
+    assert_eq!(fate.warnings(), &[Warning::Clamped { limit: Limit::Sides, max: 2 }]);
+}
+
+#[test]
+fn owned_rollers() {
+    let mut sheet = HashMap::new();
+    for &(name, roll) in &[("attack", "1d20 + 5"), ("damage", "2d6 + 3")] {
+        let syntax = roll.to_string();
+        sheet.insert(name.to_string(), syntax.parse::<Roller>().unwrap());
+    }
+
+    let damage = thread::spawn(move || sheet["damage"].clone()).join().unwrap();
+    assert_eq!(damage.to_string(), format!("[2d6 + 3: {}]", damage.total()));
+    assert_eq!("1d1 + 1".parse::<Expr>().unwrap().eval(), Ok(2));
+    assert!("food4".parse::<Expr>().is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serialized_rollers() {
+    let roller = Roller::new("4d6kh3");
+    assert_eq!(serde_json::to_string(&roller).unwrap(), r#""4d6kh3""#);
+    assert_eq!(serde_json::to_string(roller.expr()).unwrap(), r#""4d6kh3""#);
+
+    let sheet: HashMap<String, Roller> = serde_json::from_str(r#"{"damage": "2d6 + 3"}"#).unwrap();
+    let total = sheet["damage"].total();
+    assert!((5..=15).contains(&total));
+    let expr: Expr = serde_json::from_str(r#""1d1 * 3""#).unwrap();
+    assert_eq!(expr.eval(), Ok(3));
+
+    assert!(serde_json::from_str::<Roller>(r#""food4""#).is_err());
+    assert!(serde_json::from_str::<Roller>(r#""1d6 / 0""#).is_err());
+    assert!(serde_json::from_str::<Expr>("4").is_err());
+}
+
+#[test]
+fn variables() {
+    let attack = parse("1d1 + @str_mod + @{prof} * 2").unwrap();
+    let mut fighter = HashMap::new();
+    fighter.insert("str_mod".to_string(), 3);
+    fighter.insert("prof".to_string(), 2);
+    assert_eq!(attack.eval_in(&fighter), Ok(8));
+    assert_eq!(attack.roll_in(&fighter).map(|result| result.total()), Ok(8));
+
+    let bound = attack.bind(&fighter).unwrap();
+    assert_eq!(bound.as_str(), "1d1 + @str_mod + @{prof} * 2");
+    assert_eq!(bound.eval(), Ok(8));
+    assert_eq!(bound.distribution().unwrap().probability(8), 1.0);
+
+    let unknown = Error::UnknownVariable { name: "prof".to_string() };
+    fighter.remove("prof");
+    assert_eq!(attack.eval_in(&fighter), Err(unknown.clone()));
+    assert_eq!(attack.eval(), Err(Error::UnknownVariable { name: "str_mod".to_string() }));
+    assert_eq!(attack.max(), Err(Error::UnknownVariable { name: "str_mod".to_string() }));
+    assert_eq!(unknown.to_string(), "unknown variable @{prof}");
+
+    let mut wizard = std::collections::BTreeMap::new();
+    wizard.insert("int", -1);
+    assert_eq!(parse("@int + (@{int} - 1d1)").unwrap().eval_in(&wizard), Ok(-3));
+    assert!(parse("@1st").is_err());
+
+    wizard.insert("mod", -1);
+    let mut check = Roller::new_in("2d1 + @mod", &wizard);
+    assert_eq!(check.total(), 1);
+    wizard.insert("mod", 4);
+    assert_eq!(check.reroll_in(&wizard), 6);
+    assert_eq!(check.expr().as_str(), "2d1 + @mod");
+    assert_eq!(roller_or_fail("2d1 + @mod").err(), Some(Error::UnknownVariable { name: "mod".to_string() }));
+}
+
+#[test]
+#[should_panic(expected = "Failed to roll: unknown variable @{mod}")]
+fn unbound_variables_panic() {
+    Roller::new("1d20 + @mod");
+}
+
+#[test]
+fn functions() {
+    assert_eq!(parse("floor(7 / 2)").unwrap().eval(), Ok(3));
+    assert_eq!(parse("ceil(7 / 2)").unwrap().eval(), Ok(4));
+    assert_eq!(parse("round(7 / 2)").unwrap().eval(), Ok(4));
+    assert_eq!(parse("floor(-7 / 2)").unwrap().eval(), Ok(-4));
+    assert_eq!(parse("ceil(-7 / 2)").unwrap().eval(), Ok(-3));
+    assert_eq!(parse("round(-7 / 2)").unwrap().eval(), Ok(-4));
+    assert_eq!(parse("round(7 / 3)").unwrap().eval(), Ok(2));
+    assert_eq!(parse("FLOOR(6 / 3) + ceil(5)").unwrap().eval(), Ok(7));
+    assert_eq!(parse("abs(2 - 7) + abs(3)").unwrap().eval(), Ok(8));
+    assert_eq!(parse("min(4, 2, 7) + max(-1)").unwrap().eval(), Ok(1));
+    assert_eq!(parse("ceil(1 / 0)").unwrap().eval(), Err(Error::DivisionByZero));
+    assert_eq!(parse("abs(-9223372036854775807 - 1)").unwrap().eval(), Err(Error::Overflow));
+
+    // The argument is worked out exactly, and only rounded at the end
+    assert_eq!(parse("floor(-7/2 + 0)").unwrap().eval(), Ok(-4));
+    assert_eq!(parse("round(5/2 + 1)").unwrap().eval(), Ok(4));
+    assert_eq!(parse("floor(7/2 + 7/2)").unwrap().eval(), Ok(7));
+    assert_eq!(parse("ceil(-(1/3) * 3 - 1/2)").unwrap().eval(), Ok(-1));
+    assert_eq!(parse("floor(7/2 % 2)").unwrap().eval(), Ok(1));
+    assert_eq!(parse("floor(1 / (1/2 - 1/2))").unwrap().eval(), Err(Error::DivisionByZero));
+    let rounded = parse("round(1d6 / 4 + 1d2 / 4)").unwrap();
+    assert_eq!((rounded.min(), rounded.max()), (Ok(1), Ok(2)));
+    assert!((rounded.distribution().unwrap().probability(2) - 5.0 / 12.0).abs() < 1e-12);
+    assert_eq!(parse("floor(1 / (1d2 - 3/2))").unwrap().min(), Ok(-2));
+
+    let advantage = parse("max(1d20, 1d20)").unwrap();
+    let odds = advantage.distribution().unwrap();
+    assert!((odds.probability(20) - 39.0 / 400.0).abs() < 1e-12);
+    assert_eq!((advantage.min(), advantage.max()), (Ok(1), Ok(20)));
+    assert_eq!(parse("abs(1d6 - 4)").unwrap().max(), Ok(3));
+    assert_eq!(parse("abs(1d6 - 4)").unwrap().min(), Ok(0));
+    let halved = parse("floor(1d6 / 2)").unwrap();
+    assert_eq!((halved.min(), halved.max()), (Ok(0), Ok(3)));
+    assert!((halved.mean().unwrap() - 1.5).abs() < 1e-12);
+    assert!((parse("ceil(1d6 / 2)").unwrap().mean().unwrap() - 2.0).abs() < 1e-12);
+
+    let roller = Roller::new("max(2d1, 3) + 1");
+    assert_eq!(roller.result().render(Style::Plain), "max((1+1), 3)+1 = 4");
+
+    assert!(parse("floor(1, 2)").is_err());
+    assert!(parse("min()").is_err());
+}
+
+#[test]
+fn operators() {
+    assert_eq!(roll_dice_or_fail("17 % 5"), Ok(2));
+    assert_eq!(roll_dice_or_fail("-1 % 12"), Ok(11));
+    assert_eq!(roll_dice_or_fail("7 % -3"), Ok(1));
+    assert_eq!(roll_dice_or_fail("2 + 10 % 4 * 3"), Ok(8));
+    assert_eq!(roll_dice_or_fail("2 ** 10"), Ok(1024));
+    assert_eq!(roll_dice_or_fail("2 ^ 3 ^ 2"), Ok(512));
+    assert_eq!(roll_dice_or_fail("(2 ^ 3) ^ 2"), Ok(64));
+    assert_eq!(roll_dice_or_fail("3 * 2 ^ 2"), Ok(12));
+    assert_eq!(roll_dice_or_fail("2 ^ -1 + 1 ^ -1 + (-1) ^ -3 + 0 ^ 0"), Ok(1));
+    assert_eq!(roll_dice_or_fail("5 % 0"), Err(Error::DivisionByZero));
+    assert_eq!(roll_dice_or_fail("0 ^ -1"), Err(Error::DivisionByZero));
+    assert_eq!(roll_dice_or_fail("2 ^ 63"), Err(Error::Overflow));
+    assert_eq!(roll_dice_or_fail("10 ^ 10000000000"), Err(Error::Overflow));
+    assert_eq!(roll_dice_or_fail("-9223372036854775808 % -1"), Err(Error::Overflow));
+
+    assert_eq!(roll_dice_or_fail("-(2d1)"), Ok(-2));
+    assert_eq!(roll_dice_or_fail("-1d1 + 5"), Ok(4));
+    assert_eq!(roll_dice_or_fail("10 - -(1 + 2)"), Ok(13));
+    assert_eq!(roll_dice_or_fail("- -3"), Ok(3));
+    assert_eq!(roll_dice_or_fail("-2 ^ 2"), Ok(-4));
+    assert_eq!(roll_dice_or_fail("-2^2 * 3"), Ok(-12));
+    assert_eq!(roll_dice_or_fail("(-2) ^ 2"), Ok(4));
+    assert_eq!(roll_dice_or_fail("2 ^ -2 ^ 2 + 3 ^ - 1d1"), Ok(0));
+    assert_eq!(roll_dice_or_fail("-1d1 ^ 2"), Ok(-1));
+    assert_eq!(
+        roll_dice_or_fail(&format!("{}1", "-".repeat(1000))),
+        Err(Error::LimitExceeded { limit: Limit::Depth, max: 64 })
+    );
+    let too_deep = roll_dice_or_fail(&format!("{}1", "-".repeat(70))).unwrap_err();
+    assert_eq!(too_deep.to_string(), "too many levels of nesting (the limit is 64)");
+    assert_eq!(roll_dice_or_fail("-(-9223372036854775808)"), Err(Error::Overflow));
+    for _ in 0..100 {
+        assert_range!(-22 => roll_dice("1d20 - -1d4 - 2d4 * 3") => 18);
+    }
+
+    let penalty = parse("1d20 - 1d4").unwrap();
+    let negated = parse("1d20 + -(1d4)").unwrap();
+    assert_eq!(penalty.distribution(), negated.distribution());
+    assert_eq!((negated.min(), negated.max()), (Ok(-3), Ok(19)));
+    assert!((negated.mean().unwrap() - 8.0).abs() < 1e-12);
+
+    let d66 = parse("1d66 % 6").unwrap();
+    assert_eq!((d66.min(), d66.max()), (Ok(0), Ok(5)));
+    assert!((d66.distribution().unwrap().probability(0) - 11.0 / 66.0).abs() < 1e-12);
+    let squared = parse("(1d5 - 3) ^ 2").unwrap();
+    assert_eq!((squared.min(), squared.max()), (Ok(0), Ok(4)));
+    assert_eq!(parse("1d6 % (1d3 - 1)").unwrap().max(), Err(Error::DivisionByZero));
+
+    assert_eq!(Roller::new("-(2d1 + 1)").result().to_string(), "-((1+1)+1) = -3");
+    assert_eq!(Roller::new("-2d1").result().to_string(), "-(1+1) = -2");
+    assert_eq!(Roller::new("(1d1 + 1) ^ 2 ^ 2").result().to_string(), "((1)+1)^2^2 = 16");
+    assert_eq!(Roller::new("(2 ^ 1d1) ^ 2").result().to_string(), "(2^(1))^2 = 4");
+    assert_eq!(Roller::new("(-2) ^ 1d1").result().to_string(), "(-2)^(1) = -2");
+    assert_eq!(Roller::new("-(2) ^ 1d1").result().to_string(), "-(2^(1)) = -2");
+    assert_eq!(Roller::new("2 * (7 % 3d1)").result().to_string(), "2*(7%(1+1+1)) = 2");
+    assert_eq!(Roller::new("1d1 ** 2").result().render(Style::Markdown), "(1)\\^2 = 1");
+}
+
+#[test]
+fn comparisons() {
+    assert_eq!(roll_dice_or_fail("3d1 + 5 >= 8"), Ok(1));
+    assert_eq!(roll_dice_or_fail("3d1 + 5 > 8"), Ok(0));
+    assert_eq!(roll_dice_or_fail("2 * 3 == 6"), Ok(1));
+    assert_eq!(roll_dice_or_fail("1 < 2 and 2 <= 1 or 3 > 2"), Ok(1));
+    assert_eq!(roll_dice_or_fail("1 < 2 AND (2 <= 1 OR 3 < 2)"), Ok(0));
+    assert_eq!(roll_dice_or_fail("(1d1 >= 1) + (1d1 >= 2) + (1d1 >= 0)"), Ok(2));
+    assert_eq!(roll_dice_or_fail("4d1>1"), Ok(0));
+    assert_eq!(roll_dice_or_fail("4d1 > 1"), Ok(1));
+
+    // Success targets where a comparison could have been meant are warned about
+    let counted = Warning::SuccessCount { notation: "1d20>=15".to_string() };
+    assert_eq!(parse("1d20>=15").unwrap().warnings().to_vec(), vec![counted.clone()]);
+    assert_eq!(parse("1d20>=15 and 1d20 >= 15").unwrap().warnings().to_vec(), vec![counted.clone()]);
+    assert_eq!(counted.to_string(), "`1d20>=15` counts the dice meeting its target, rather than comparing their total");
+    assert!(parse("1d20 >= 15").unwrap().warnings().is_empty());
+    assert!(parse("5d10>=8 + 1").unwrap().warnings().is_empty());
+    assert!(parse("max(2d20>=15, 1)").unwrap().warnings().is_empty());
+
+    let hit = parse("1d1 + 17 >= 15").unwrap().roll().unwrap();
+    assert_eq!((hit.success(), hit.margin()), (Some(true), Some(3)));
+    let mut fighter = HashMap::new();
+    fighter.insert("mod", 2);
+    let miss = parse("2d1 + @mod > 6").unwrap().roll_in(&fighter).unwrap();
+    assert_eq!((miss.success(), miss.margin()), (Some(false), Some(-3)));
+    let low = parse("1d1 <= 3").unwrap().roll().unwrap();
+    assert_eq!((low.success(), low.margin()), (Some(true), Some(2)));
+    let exact = parse("1d1 == 3").unwrap().roll().unwrap();
+    assert_eq!((exact.success(), exact.margin()), (Some(false), Some(-2)));
+    let both = parse("1d1 >= 1 and 1d1 + 4 > 2").unwrap().roll().unwrap();
+    assert_eq!((both.success(), both.margin()), (Some(true), Some(0)));
+    let either = parse("1d1 > 4 or 5 > 1d1").unwrap().roll().unwrap();
+    assert_eq!((either.success(), either.margin()), (Some(true), Some(3)));
+    let tie = parse("1d1 + 14 > 15").unwrap().roll().unwrap();
+    assert_eq!((tie.success(), tie.margin()), (Some(false), Some(-1)));
+    let under = parse("1d1 < 2").unwrap().roll().unwrap();
+    assert_eq!((under.success(), under.margin()), (Some(true), Some(0)));
+    let mixed = parse("0 and 1d1 + 5 > 4").unwrap().roll().unwrap();
+    assert_eq!((mixed.success(), mixed.margin()), (Some(false), None));
+    let mixed = parse("1d1 > 4 or 1").unwrap().roll().unwrap();
+    assert_eq!((mixed.success(), mixed.margin()), (Some(true), None));
+    let total = parse("3d1 + 4").unwrap().roll().unwrap();
+    assert_eq!((total.success(), total.margin()), (None, None));
+
+    let attack = parse("1d20 + 7 >= 16").unwrap();
+    assert!((attack.distribution().unwrap().probability(1) - 0.6).abs() < 1e-12);
+    assert!((attack.mean().unwrap() - 0.6).abs() < 1e-12);
+    assert_eq!((attack.min(), attack.max()), (Ok(0), Ok(1)));
+    assert_eq!((parse("1d6 > 0").unwrap().min(), parse("1d6 > 6").unwrap().max()), (Ok(1), Ok(0)));
+
+    assert_eq!(Roller::new("3d1 + 5 >= 9").result().to_string(), "(1+1+1)+5>=9 = 0");
+    assert_eq!(Roller::new("1d1 > 0 and (1 < 2 or 2 < 1)").result().to_string(), "(1)>0 and (1<2 or 2<1) = 1");
+}